@@ -0,0 +1,100 @@
+//! Compares the built-in timer with a single sorted map under one global mutex,
+//! which is how the timer was implemented before it used timer wheels.
+//!
+//! Run with `cargo bench --bench timer`.
+
+use std::{
+    collections::BTreeMap,
+    hint::black_box,
+    sync::{Mutex, atomic::AtomicU64, atomic::Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use sigwake::{time::spawn_at, utils::Action};
+
+static BASELINE: Mutex<BTreeMap<(Instant, u64), Action>> = Mutex::new(BTreeMap::new());
+static BASELINE_ID: AtomicU64 = AtomicU64::new(0);
+
+struct BaselineTask {
+    at: Instant,
+    id: u64,
+}
+impl Drop for BaselineTask {
+    fn drop(&mut self) {
+        BASELINE.lock().unwrap().remove(&(self.at, self.id));
+    }
+}
+fn baseline_spawn_at(action: Action, at: Instant) -> BaselineTask {
+    let id = BASELINE_ID.fetch_add(1, Ordering::Relaxed);
+    BASELINE.lock().unwrap().insert((at, id), action);
+    BaselineTask { at, id }
+}
+
+fn timeout(i: usize) -> Instant {
+    Instant::now() + Duration::from_secs(60) + Duration::from_micros((i % 100_000) as u64)
+}
+
+/// Schedules `count` actions and then cancels all of them.
+fn bulk(name: &str, count: usize) {
+    let start = Instant::now();
+    let tasks: Vec<_> = (0..count)
+        .map(|i| spawn_at(Action::new(|| {}), timeout(i)))
+        .collect();
+    drop(black_box(tasks));
+    let wheel = start.elapsed();
+
+    let start = Instant::now();
+    let tasks: Vec<_> = (0..count)
+        .map(|i| baseline_spawn_at(Action::new(|| {}), timeout(i)))
+        .collect();
+    drop(black_box(tasks));
+    let baseline = start.elapsed();
+    report(name, count, wheel, baseline);
+}
+
+/// Schedules and immediately cancels actions on several threads, like connection timeouts that are reset on every message.
+fn contended(name: &str, threads: usize, count: usize) {
+    let wheel = run_threads(threads, move || {
+        for i in 0..count {
+            drop(black_box(spawn_at(Action::new(|| {}), timeout(i))));
+        }
+    });
+    let baseline = run_threads(threads, move || {
+        for i in 0..count {
+            drop(black_box(baseline_spawn_at(Action::new(|| {}), timeout(i))));
+        }
+    });
+    report(name, threads * count, wheel, baseline);
+}
+
+fn run_threads(threads: usize, f: impl Fn() + Send + Copy + 'static) -> Duration {
+    let start = Instant::now();
+    let handles: Vec<_> = (0..threads).map(|_| thread::spawn(f)).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, count: usize, wheel: Duration, baseline: Duration) {
+    let per_op = |d: Duration| d.as_nanos() as f64 / count as f64;
+    println!(
+        "{name:<32} wheel: {:>8.1} ns/op   btree: {:>8.1} ns/op",
+        per_op(wheel),
+        per_op(baseline)
+    );
+}
+
+fn main() {
+    bulk("bulk 10k", 10_000);
+    bulk("bulk 100k", 100_000);
+    bulk("bulk 1M", 1_000_000);
+    for threads in [1, 4, 16] {
+        contended(
+            &format!("spawn and cancel, {threads} threads"),
+            threads,
+            1_000_000 / threads,
+        );
+    }
+}
@@ -0,0 +1,18 @@
+extern crate proc_macro;
+
+#[macro_use]
+mod syn_utils;
+
+mod state;
+
+/// Derives constructors and helpers for a state struct of `StateContainer<St>`.
+///
+/// See `sigwake::State` for details.
+#[proc_macro_derive(State, attributes(state))]
+pub fn derive_state(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    match state::build(input.into()) {
+        Ok(s) => s,
+        Err(e) => e.to_compile_error(),
+    }
+    .into()
+}
@@ -0,0 +1,369 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use structmeta::{Flag, StructMeta};
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, GenericParam, Generics, Ident, Lifetime,
+    LifetimeParam, PathArguments, Result, Type, WherePredicate, ext::IdentExt, parse_quote, parse2,
+};
+
+use crate::syn_utils::parse_state_args;
+
+#[derive(StructMeta, Default)]
+struct StructArgs {
+    debug: Flag,
+    serde: Flag,
+}
+
+#[derive(StructMeta, Default)]
+struct FieldArgs {
+    default: Flag,
+    plain: Flag,
+}
+
+struct StateField<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
+    args: FieldArgs,
+}
+impl StateField<'_> {
+    fn name(&self) -> String {
+        self.ident.unraw().to_string()
+    }
+
+    /// Returns `T` if the type of this field is `Value<T>`.
+    fn value_ty(&self) -> Option<&Type> {
+        if self.args.plain.value() {
+            return None;
+        }
+        let Type::Path(ty) = self.ty else {
+            return None;
+        };
+        let last = ty.path.segments.last()?;
+        if last.ident != "Value" {
+            return None;
+        }
+        let PathArguments::AngleBracketed(args) = &last.arguments else {
+            return None;
+        };
+        match args.args.first() {
+            Some(GenericArgument::Type(ty)) if args.args.len() == 1 => Some(ty),
+            _ => None,
+        }
+    }
+}
+
+pub fn build(input: TokenStream) -> Result<TokenStream> {
+    let input: DeriveInput = parse2(input)?;
+    let args: StructArgs = parse_state_args(&input.attrs)?;
+    let Data::Struct(data) = &input.data else {
+        bail!(
+            input.ident.span(),
+            "`#[derive(State)]` can be specified only for `struct`"
+        );
+    };
+    let Fields::Named(fields) = &data.fields else {
+        bail!(
+            input.ident.span(),
+            "`#[derive(State)]` can be specified only for struct with named fields"
+        );
+    };
+    let mut fs = Vec::new();
+    for field in &fields.named {
+        let args: FieldArgs = parse_state_args(&field.attrs)?;
+        if let (Some(span), true) = (args.default.span, args.plain.value()) {
+            bail!(span, "`default` and `plain` cannot be specified together");
+        }
+        fs.push(StateField {
+            ident: field.ident.as_ref().unwrap(),
+            ty: &field.ty,
+            args,
+        });
+    }
+
+    let mut ts = build_init(&input, &fs);
+    ts.extend(build_ext(&input, &fs));
+    if args.debug.value() {
+        ts.extend(build_debug(&input, &fs));
+    }
+    if args.serde.value() {
+        ts.extend(build_serde(&input, &fs)?);
+    }
+    Ok(ts)
+}
+
+fn build_init(input: &DeriveInput, fs: &[StateField]) -> TokenStream {
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let (impl_g, type_g, where_clause) = input.generics.split_for_impl();
+    let cx = Ident::new("cx", Span::mixed_site());
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    let mut inits = Vec::new();
+    for f in fs {
+        let fi = f.ident;
+        let ty = f.ty;
+        if f.args.default.value() {
+            inits.push(quote!(#fi: ::sigwake::state::StateInit::init(::core::default::Default::default(), #cx)));
+            continue;
+        }
+        args.push(fi);
+        if f.args.plain.value() {
+            params.push(quote!(#fi: #ty));
+            inits.push(quote!(#fi));
+        } else {
+            params.push(quote!(#fi: <#ty as ::sigwake::state::StateInit>::Init));
+            inits.push(quote!(#fi: ::sigwake::state::StateInit::init(#fi, #cx)));
+        }
+    }
+    quote! {
+        impl #impl_g #ident #type_g #where_clause {
+            /// Creates the state from the initial values of its fields.
+            #vis fn init(#cx: &mut ::sigwake::StateContext, #(#params),*) -> Self {
+                Self {
+                    #(#inits,)*
+                }
+            }
+
+            /// Creates a [`StateContainer`](::sigwake::StateContainer) from the initial values of the fields of its state.
+            #vis fn new_container(#(#params),*) -> ::sigwake::StateContainer<Self> {
+                ::sigwake::StateContainer::new(move |#cx| Self::init(#cx, #(#args),*))
+            }
+        }
+    }
+}
+
+fn build_ext(input: &DeriveInput, fs: &[StateField]) -> TokenStream {
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let generics = &input.generics;
+    let (impl_g, type_g, where_clause) = generics.split_for_impl();
+    let ext = format_ident!("{}Ext", ident);
+    let mut decls = Vec::new();
+    let mut defs = Vec::new();
+    for f in fs {
+        let Some(vt) = f.value_ty() else {
+            continue;
+        };
+        let fi = f.ident;
+        let get = f.ident;
+        let set = format_ident!("set_{}", f.ident.unraw());
+        let get_doc = format!("Returns a clone of `{}` without tracking.", f.name());
+        let set_doc = format!("Sets `{}` and notifies its dependents.", f.name());
+
+        // The higher-ranked bound keeps the trait valid even if `#vt` is not `Clone`.
+        let get_sig = quote!(fn #get(&self) -> #vt where for<'__a> #vt: ::core::clone::Clone);
+        let set_sig = quote!(fn #set(&self, value: #vt));
+        decls.push(quote! {
+            #[doc = #get_doc]
+            #get_sig;
+            #[doc = #set_doc]
+            #set_sig;
+        });
+        defs.push(quote! {
+            #get_sig {
+                ::core::clone::Clone::clone(self.lock_untracked().#fi.get_untracked())
+            }
+            #set_sig {
+                self.update(|st, cx| st.#fi.set(value, cx))
+            }
+        });
+    }
+    if decls.is_empty() {
+        return quote!();
+    }
+    let doc = format!(
+        "Getters and setters for the [`Value`](::sigwake::state::Value) fields of [`{ident}`] in a [`StateContainer`](::sigwake::StateContainer)."
+    );
+    quote! {
+        #[doc = #doc]
+        #vis trait #ext #generics #where_clause {
+            #(#decls)*
+        }
+        impl #impl_g #ext #type_g for ::sigwake::StateContainer<#ident #type_g> #where_clause {
+            #(#defs)*
+        }
+    }
+}
+
+fn build_debug(input: &DeriveInput, fs: &[StateField]) -> TokenStream {
+    let ident = &input.ident;
+    let name = ident.unraw().to_string();
+    let mut generics = input.generics.clone();
+    let mut fields = Vec::new();
+    for f in fs {
+        let fi = f.ident;
+        let name = f.name();
+        let (ty, value) = match f.value_ty() {
+            Some(vt) => (vt, quote!(self.#fi.get_untracked())),
+            None => (f.ty, quote!(&self.#fi)),
+        };
+        push_bound(&mut generics, parse_quote!(#ty: ::core::fmt::Debug));
+        fields.push(quote!(.field(#name, #value)));
+    }
+    let (impl_g, type_g, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_g ::core::fmt::Debug for #ident #type_g #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_struct(#name) #(#fields)* .finish()
+            }
+        }
+    }
+}
+
+fn build_serde(input: &DeriveInput, fs: &[StateField]) -> Result<TokenStream> {
+    if let Some(p) = input.generics.lifetimes().next() {
+        bail!(
+            p.lifetime.span(),
+            "`#[state(serde)]` cannot be specified for struct with lifetime parameters"
+        );
+    }
+    let ident = &input.ident;
+    let name = ident.unraw().to_string();
+    let serde = quote!(::sigwake::__private::serde);
+    let names: Vec<_> = fs.iter().map(|f| f.name()).collect();
+    let fis: Vec<_> = fs.iter().map(|f| f.ident).collect();
+    let tys: Vec<_> = fs.iter().map(|f| f.ty).collect();
+    let indexes = 0..fs.len();
+    let len = fs.len();
+
+    let mut ser_generics = input.generics.clone();
+    for ty in &tys {
+        push_bound(&mut ser_generics, parse_quote!(#ty: #serde::Serialize));
+    }
+    let (ser_impl_g, type_g, ser_where) = ser_generics.split_for_impl();
+
+    let de = Lifetime::new("'de", Span::call_site());
+    let mut de_generics = strip_defaults(&input.generics);
+    de_generics
+        .params
+        .insert(0, GenericParam::Lifetime(LifetimeParam::new(de.clone())));
+    for ty in &tys {
+        push_bound(
+            &mut de_generics,
+            parse_quote!(#ty: ::sigwake::DeserializeState<#de>),
+        );
+    }
+    let (de_impl_g, _, de_where) = de_generics.split_for_impl();
+
+    let a = Lifetime::new("'__a", Span::call_site());
+    let mut visitor_generics = strip_defaults(&input.generics);
+    visitor_generics
+        .params
+        .insert(0, GenericParam::Lifetime(LifetimeParam::new(a.clone())));
+    let (_, visitor_type_g, visitor_struct_where) = visitor_generics.split_for_impl();
+    let mut visitor_impl_generics = visitor_generics.clone();
+    visitor_impl_generics
+        .params
+        .insert(0, GenericParam::Lifetime(LifetimeParam::new(de.clone())));
+    visitor_impl_generics.where_clause = de_generics.where_clause.clone();
+    let (visitor_impl_g, _, visitor_where) = visitor_impl_generics.split_for_impl();
+
+    let cx = Ident::new("cx", Span::mixed_site());
+    let serializer = Ident::new("serializer", Span::mixed_site());
+    let deserializer = Ident::new("deserializer", Span::mixed_site());
+    let s = Ident::new("s", Span::mixed_site());
+    let seq = Ident::new("seq", Span::mixed_site());
+    let map = Ident::new("map", Span::mixed_site());
+    let key = Ident::new("key", Span::mixed_site());
+    let visitor = Ident::new("Visitor", Span::mixed_site());
+    let expecting = format!("struct {name}");
+
+    Ok(quote! {
+        impl #ser_impl_g #serde::Serialize for #ident #type_g #ser_where {
+            fn serialize<__S: #serde::Serializer>(&self, #serializer: __S) -> ::core::result::Result<__S::Ok, __S::Error> {
+                let mut #s = #serde::Serializer::serialize_struct(#serializer, #name, #len)?;
+                #(#serde::ser::SerializeStruct::serialize_field(&mut #s, #names, &self.#fis)?;)*
+                #serde::ser::SerializeStruct::end(#s)
+            }
+        }
+        impl #de_impl_g ::sigwake::DeserializeState<#de> for #ident #type_g #de_where {
+            fn deserialize_state<__D: #serde::Deserializer<#de>>(
+                #deserializer: __D,
+                #cx: &mut ::sigwake::StateContext,
+            ) -> ::core::result::Result<Self, __D::Error> {
+                struct #visitor #visitor_generics (
+                    &#a mut ::sigwake::StateContext,
+                    ::core::marker::PhantomData<fn() -> #ident #type_g>,
+                ) #visitor_struct_where;
+                impl #visitor_impl_g #serde::de::Visitor<#de> for #visitor #visitor_type_g #visitor_where {
+                    type Value = #ident #type_g;
+
+                    fn expecting(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        f.write_str(#expecting)
+                    }
+                    fn visit_seq<__A: #serde::de::SeqAccess<#de>>(
+                        self,
+                        mut #seq: __A,
+                    ) -> ::core::result::Result<Self::Value, __A::Error> {
+                        #(
+                            let #fis = #serde::de::SeqAccess::next_element_seed(
+                                &mut #seq,
+                                ::sigwake::StateSeed::<#tys>::new(self.0),
+                            )?
+                            .ok_or_else(|| #serde::de::Error::invalid_length(#indexes, &#expecting))?;
+                        )*
+                        ::core::result::Result::Ok(#ident { #(#fis,)* })
+                    }
+                    fn visit_map<__A: #serde::de::MapAccess<#de>>(
+                        self,
+                        mut #map: __A,
+                    ) -> ::core::result::Result<Self::Value, __A::Error> {
+                        #(let mut #fis: ::core::option::Option<#tys> = ::core::option::Option::None;)*
+                        while let ::core::option::Option::Some(#key) =
+                            #serde::de::MapAccess::next_key::<::std::string::String>(&mut #map)?
+                        {
+                            match #key.as_str() {
+                                #(
+                                    #names => {
+                                        if #fis.is_some() {
+                                            return ::core::result::Result::Err(#serde::de::Error::duplicate_field(#names));
+                                        }
+                                        #fis = ::core::option::Option::Some(#serde::de::MapAccess::next_value_seed(
+                                            &mut #map,
+                                            ::sigwake::StateSeed::<#tys>::new(self.0),
+                                        )?);
+                                    }
+                                )*
+                                _ => {
+                                    #serde::de::MapAccess::next_value::<#serde::de::IgnoredAny>(&mut #map)?;
+                                }
+                            }
+                        }
+                        #(
+                            let #fis = #fis.ok_or_else(|| #serde::de::Error::missing_field(#names))?;
+                        )*
+                        ::core::result::Result::Ok(#ident { #(#fis,)* })
+                    }
+                }
+                #serde::Deserializer::deserialize_struct(
+                    #deserializer,
+                    #name,
+                    &[#(#names),*],
+                    #visitor(#cx, ::core::marker::PhantomData),
+                )
+            }
+        }
+    })
+}
+
+fn push_bound(generics: &mut Generics, predicate: WherePredicate) {
+    generics.make_where_clause().predicates.push(predicate);
+}
+
+fn strip_defaults(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    for p in &mut generics.params {
+        match p {
+            GenericParam::Type(p) => {
+                p.eq_token = None;
+                p.default = None;
+            }
+            GenericParam::Const(p) => {
+                p.eq_token = None;
+                p.default = None;
+            }
+            GenericParam::Lifetime(_) => {}
+        }
+    }
+    generics
+}
@@ -0,0 +1,27 @@
+use syn::{Attribute, Result, parse::Parse};
+
+macro_rules! bail {
+    ($span:expr, $fmt:expr $(,)?) => {
+        return std::result::Result::Err(syn::Error::new($span, std::format!($fmt)))
+    };
+    ($span:expr, $fmt:expr, $($arg:tt)*) => {
+        return std::result::Result::Err(syn::Error::new($span, std::format!($fmt, $($arg)*)))
+    };
+}
+
+/// Parses the arguments of the only `#[state(...)]` attribute in `attrs`.
+pub fn parse_state_args<T: Parse + Default>(attrs: &[Attribute]) -> Result<T> {
+    let mut args = None;
+    for attr in attrs {
+        if attr.path().is_ident("state") {
+            if args.is_some() {
+                bail!(
+                    attr.pound_token.span,
+                    "`#[state]` is specified more than once"
+                );
+            }
+            args = Some(attr.parse_args()?);
+        }
+    }
+    Ok(args.unwrap_or_default())
+}
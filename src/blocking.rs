@@ -0,0 +1,126 @@
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    ContainerRef, StateContainer, StateContext, Target, Timeout,
+    time::{AnyTime, TimerBackend, spawn_at_with},
+};
+
+impl<St> StateContainer<St> {
+    /// Blocks the current thread until `f` returns `Poll::Ready`.
+    ///
+    /// The blocking counterpart of [`poll_fn`](Self::poll_fn) for threads that are not driven by an async runtime.
+    /// The calling thread is parked while the states read by `f` are unchanged.
+    pub fn wait_blocking<U>(&self, f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>) -> U {
+        match self.wait_blocking_raw(None, f) {
+            Ok(value) => value,
+            Err(Timeout) => unreachable!(),
+        }
+    }
+
+    /// Blocks the current thread until `f` returns `Poll::Ready` or `deadline` is reached.
+    pub fn wait_blocking_until<U>(
+        &self,
+        deadline: impl Into<AnyTime>,
+        f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>,
+    ) -> Result<U, Timeout> {
+        self.wait_blocking_raw(Some(deadline.into()), f)
+    }
+    fn wait_blocking_raw<U>(
+        &self,
+        deadline: Option<AnyTime>,
+        mut f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>,
+    ) -> Result<U, Timeout> {
+        let timer = self.with(|g, _| g.timer_backend());
+        let mut t = Target::new(self);
+        block_on(timer.as_ref(), deadline, |cx| t.poll_fn(&mut f, cx))
+    }
+
+    /// Returns an iterator that blocks the current thread until the value returned by `f` may have changed.
+    ///
+    /// The blocking counterpart of [`subscribe`](Self::subscribe).
+    pub fn subscribe_blocking<U>(
+        &self,
+        f: impl FnMut(&mut St, &mut StateContext) -> U + 'static,
+    ) -> BlockingStream<impl Stream<Item = U> + 'static>
+    where
+        St: 'static,
+    {
+        let st = self.clone();
+        BlockingStream {
+            s: Box::pin(self.subscribe(f)),
+            timer: Some(Box::new(move || st.with(|g, _| g.timer_backend()))),
+        }
+    }
+}
+
+type GetTimer = Box<dyn Fn() -> Option<Arc<dyn TimerBackend>>>;
+
+/// An iterator that blocks the current thread to receive items from a [`Stream`].
+pub struct BlockingStream<S> {
+    s: Pin<Box<S>>,
+    /// Returns the timer backend of the container that the stream reads, used for deadlines.
+    timer: Option<GetTimer>,
+}
+
+impl<S: Stream> BlockingStream<S> {
+    pub fn new(s: S) -> Self {
+        Self {
+            s: Box::pin(s),
+            timer: None,
+        }
+    }
+
+    /// Blocks the current thread until the next item is available or `deadline` is reached.
+    ///
+    /// For a stream created by [`StateContainer::subscribe_blocking`], the deadline uses
+    /// the [`TimerBackend`] of the container.
+    pub fn next_until(&mut self, deadline: impl Into<AnyTime>) -> Result<Option<S::Item>, Timeout> {
+        let timer = self.timer.as_ref().and_then(|timer| timer());
+        block_on(timer.as_ref(), Some(deadline.into()), |cx| {
+            self.s.poll_next_unpin(cx)
+        })
+    }
+}
+impl<S: Stream> Iterator for BlockingStream<S> {
+    type Item = S::Item;
+    fn next(&mut self) -> Option<Self::Item> {
+        block_on(None, None, |cx| self.s.poll_next_unpin(cx)).ok()?
+    }
+}
+
+pub(crate) fn block_on<T>(
+    timer: Option<&Arc<dyn TimerBackend>>,
+    deadline: Option<AnyTime>,
+    mut poll: impl FnMut(&mut Context) -> Poll<T>,
+) -> Result<T, Timeout> {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let _task = deadline.map(|deadline| spawn_at_with(timer, (&waker).into(), deadline));
+    loop {
+        if let Poll::Ready(value) = poll(&mut cx) {
+            return Ok(value);
+        }
+        if deadline.is_some_and(|deadline| deadline.is_ready()) {
+            return Err(Timeout);
+        }
+        thread::park();
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Write},
+};
+
+/// A snapshot of the dependencies between the states and the targets of a container, for debugging.
+///
+/// Sources are [`StateKey`](crate::StateKey)s, and targets are the waiting [`poll_fn`](crate::StateContainer::poll_fn)s,
+/// [`subscribe`](crate::StateContainer::subscribe) streams, effects and memos.
+/// Use [`to_dot`](Self::to_dot) or [`to_json`](Self::to_json) to inspect it with other tools.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyGraph {
+    pub sources: Vec<SourceNode>,
+    pub targets: Vec<TargetNode>,
+}
+
+/// A [`StateKey`](crate::StateKey) in a [`DependencyGraph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceNode {
+    pub id: usize,
+    /// The name given by [`StateKey::new_named`](crate::StateKey::new_named).
+    pub name: Option<Cow<'static, str>>,
+}
+
+/// A target in a [`DependencyGraph`], which is woken when any of its sources is notified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetNode {
+    pub id: usize,
+    /// The ids of the sources that this target depends on.
+    pub sources: Vec<usize>,
+    /// The id of the source whose value this target computes, if this target belongs to a memo.
+    pub derived: Option<usize>,
+    /// Whether a source has been notified since this target was registered.
+    pub is_woken: bool,
+}
+
+impl DependencyGraph {
+    /// Returns the graph in the Graphviz DOT format.
+    ///
+    /// Sources are drawn as ellipses and targets as boxes, with edges from each source to its dependents.
+    /// A dashed edge goes from a memo's target to the source it computes.
+    pub fn to_dot(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "digraph sigwake {{").unwrap();
+        for source in &self.sources {
+            let label = match &source.name {
+                Some(name) => Cow::Borrowed(&**name),
+                None => Cow::Owned(format!("#{}", source.id)),
+            };
+            writeln!(
+                s,
+                "  s{} [label={} shape=ellipse];",
+                source.id,
+                Quoted(&label)
+            )
+            .unwrap();
+        }
+        for target in &self.targets {
+            let style = if target.is_woken { " style=bold" } else { "" };
+            writeln!(
+                s,
+                "  t{} [label=\"target {}\" shape=box{style}];",
+                target.id, target.id
+            )
+            .unwrap();
+            for x in &target.sources {
+                writeln!(s, "  s{x} -> t{};", target.id).unwrap();
+            }
+            if let Some(x) = target.derived {
+                writeln!(s, "  t{} -> s{x} [style=dashed];", target.id).unwrap();
+            }
+        }
+        writeln!(s, "}}").unwrap();
+        s
+    }
+
+    /// Returns the graph in JSON.
+    ///
+    /// The output has the form `{"sources":[{"id":0,"name":"a"}],"targets":[{"id":0,"sources":[0],"derived":null,"is_woken":false}]}`.
+    pub fn to_json(&self) -> String {
+        let mut s = String::new();
+        s.push_str("{\"sources\":[");
+        for (i, source) in self.sources.iter().enumerate() {
+            if i != 0 {
+                s.push(',');
+            }
+            write!(s, "{{\"id\":{},\"name\":", source.id).unwrap();
+            match &source.name {
+                Some(name) => write!(s, "{}", Quoted(name)).unwrap(),
+                None => s.push_str("null"),
+            }
+            s.push('}');
+        }
+        s.push_str("],\"targets\":[");
+        for (i, target) in self.targets.iter().enumerate() {
+            if i != 0 {
+                s.push(',');
+            }
+            write!(s, "{{\"id\":{},\"sources\":[", target.id).unwrap();
+            for (i, x) in target.sources.iter().enumerate() {
+                if i != 0 {
+                    s.push(',');
+                }
+                write!(s, "{x}").unwrap();
+            }
+            s.push_str("],\"derived\":");
+            match target.derived {
+                Some(x) => write!(s, "{x}").unwrap(),
+                None => s.push_str("null"),
+            }
+            write!(s, ",\"is_woken\":{}}}", target.is_woken).unwrap();
+        }
+        s.push_str("]}");
+        s
+    }
+}
+
+/// Writes a string literal that is valid in both DOT and JSON.
+struct Quoted<'a>(&'a str);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
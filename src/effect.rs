@@ -0,0 +1,173 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use crate::{
+    StateContainer, StateContext, Target,
+    blocking::block_on,
+    utils::{Action, trace::trace_span},
+};
+
+type Cleanup = Box<dyn FnOnce() + Send>;
+type EffectFn<St> = Box<dyn FnMut(&mut St, &mut StateContext) -> Option<Cleanup> + Send>;
+
+impl<St> StateContainer<St> {
+    /// Creates an effect that runs `f`, and runs it again whenever the states read by `f` change.
+    ///
+    /// The effect does nothing until it is started with [`Effect::spawn`] or [`Effect::spawn_thread`].
+    pub fn effect(
+        &self,
+        mut f: impl FnMut(&mut St, &mut StateContext) + Send + 'static,
+    ) -> Effect<St> {
+        Effect::new(
+            self,
+            Box::new(move |st, cx| {
+                f(st, cx);
+                None
+            }),
+        )
+    }
+
+    /// Like [`effect`](Self::effect), but `f` returns a cleanup closure.
+    ///
+    /// The cleanup closure is called before the next run of `f` and when the effect stops,
+    /// after the container lock is released.
+    pub fn effect_with_cleanup<C: FnOnce() + Send + 'static>(
+        &self,
+        mut f: impl FnMut(&mut St, &mut StateContext) -> C + Send + 'static,
+    ) -> Effect<St> {
+        Effect::new(self, Box::new(move |st, cx| Some(Box::new(f(st, cx)))))
+    }
+}
+
+/// An effect created by [`StateContainer::effect`] that has not been started yet.
+#[must_use = "effects do nothing unless spawned"]
+pub struct Effect<St>(EffectTask<St>);
+
+impl<St> Effect<St> {
+    fn new(st: &StateContainer<St>, f: EffectFn<St>) -> Self {
+        Self(EffectTask {
+            t: Target::new(st),
+            f,
+            cleanup: None,
+            state: Arc::new(Mutex::new(EffectState {
+                waker: None,
+                age: 0,
+                is_dirty: true,
+                is_stopped: false,
+            })),
+        })
+    }
+
+    /// Starts the effect by passing the task that runs it to `spawner`.
+    ///
+    /// This works with any executor, for example `effect.spawn(|task| { tokio::spawn(task); })`.
+    pub fn spawn(self, spawner: impl FnOnce(EffectTask<St>)) -> EffectHandle {
+        let handle = EffectHandle(self.0.state.clone());
+        spawner(self.0);
+        handle
+    }
+
+    /// Starts the effect on a dedicated thread, which exits when the effect stops.
+    pub fn spawn_thread(self) -> EffectHandle
+    where
+        St: Send + 'static,
+    {
+        self.spawn(|mut task| {
+            thread::spawn(move || block_on(None, None, |cx| Pin::new(&mut task).poll(cx)));
+        })
+    }
+}
+
+/// A handle to a running effect. Dropping it stops the effect.
+#[derive(Debug)]
+pub struct EffectHandle(Arc<Mutex<EffectState>>);
+
+impl Drop for EffectHandle {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap();
+        state.is_stopped = true;
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The task that runs an effect, which completes when its [`EffectHandle`] is dropped.
+#[must_use = "futures do nothing unless polled"]
+pub struct EffectTask<St> {
+    t: Target<StateContainer<St>>,
+    f: EffectFn<St>,
+    cleanup: Option<Cleanup>,
+    state: Arc<Mutex<EffectState>>,
+}
+
+#[derive(Debug)]
+struct EffectState {
+    waker: Option<Waker>,
+    age: usize,
+    is_dirty: bool,
+    is_stopped: bool,
+}
+
+fn wake(state: Arc<Mutex<EffectState>>, age: usize) {
+    let mut state = state.lock().unwrap();
+    if state.age == age {
+        state.is_dirty = true;
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<St> Future for EffectTask<St> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.state.lock().unwrap();
+        if state.is_stopped {
+            drop(state);
+            if let Some(cleanup) = this.cleanup.take() {
+                cleanup();
+            }
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        if !state.is_dirty {
+            return Poll::Pending;
+        }
+        state.is_dirty = false;
+        state.age = state.age.wrapping_add(1);
+        let age = state.age;
+        drop(state);
+        let _span = trace_span!("effect");
+        if let Some(cleanup) = this.cleanup.take() {
+            cleanup();
+        }
+        let cleanup = this.t.poll_run(cx, &mut this.f, || {
+            Action::from_arc_fn_usize(this.state.clone(), wake, age)
+        });
+        match cleanup {
+            Poll::Ready(cleanup) => this.cleanup = cleanup,
+            // The container is locked, so run again when it is released.
+            Poll::Pending => this.state.lock().unwrap().is_dirty = true,
+        }
+        Poll::Pending
+    }
+}
+impl<St> Drop for EffectTask<St> {
+    fn drop(&mut self) {
+        if let Some(cleanup) = self.cleanup.take() {
+            cleanup();
+        }
+    }
+}
@@ -1,8 +1,64 @@
+mod blocking;
+mod dependency_graph;
+mod effect;
+mod local_state_container;
+mod rw_state_container;
+#[cfg(feature = "serde")]
+mod snapshot;
 pub mod state;
 mod state_container;
+mod stats;
 pub mod time;
 pub mod utils;
 
+pub use blocking::BlockingStream;
+pub use dependency_graph::{DependencyGraph, SourceNode, TargetNode};
+pub use effect::{Effect, EffectHandle, EffectTask};
+pub use local_state_container::LocalStateContainer;
+pub use rw_state_container::RwStateContainer;
+#[cfg(feature = "serde")]
+pub use snapshot::{DeserializeState, StateSeed};
+pub use stats::ContainerStats;
+
+/// Derives constructors and helpers for a state struct.
+///
+/// Each field is created with [`StateInit`](state::StateInit) from a plain initial value, and the following items are generated.
+///
+/// - `fn init(cx: &mut StateContext, field1, field2, ..) -> Self`, which creates the state.
+/// - `fn new_container(field1, field2, ..) -> StateContainer<Self>`, which creates a [`StateContainer`] holding the state.
+/// - A trait `{Name}Ext` implemented for `StateContainer<Name>`, with a getter `field()` and a setter `set_field(value)`
+///   for each field of type [`Value<T>`](state::Value). The getter reads without tracking and requires `T: Clone`.
+///
+/// # Attributes
+///
+/// - `#[state(debug)]` on the struct implements [`Debug`](std::fmt::Debug), reading each [`Value`](state::Value) without tracking.
+/// - `#[state(serde)]` on the struct implements [`serde::Serialize`] and [`DeserializeState`] for use with
+///   [`StateContainer::snapshot`] and [`StateContainer::restore`]. This requires the `serde` feature.
+/// - `#[state(default)]` on a field creates it from the default initial value instead of a parameter.
+/// - `#[state(plain)]` on a field that is not a state type takes it as a parameter as is.
+///
+/// ```
+/// use sigwake::{State, state::{Queue, Value}};
+///
+/// #[derive(State)]
+/// struct St {
+///     a: Value<u32>,
+///     #[state(default)]
+///     queue: Queue<u32>,
+/// }
+///
+/// let st = St::new_container(1);
+/// st.set_a(2);
+/// assert_eq!(st.a(), 2);
+/// ```
+#[cfg(feature = "derive")]
+pub use sigwake_derive::State;
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+}
 pub use state_container::*;
 
 mod tests_readme;
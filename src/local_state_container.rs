@@ -0,0 +1,172 @@
+use std::{
+    cell::{Ref, RefCell},
+    rc::Rc,
+    task::Poll,
+};
+
+use derive_ex::Ex;
+use futures::Stream;
+
+use crate::{
+    ContainerPoisoned, ContainerRef, ContainerStats, DependencyGraph, RawStateContainer,
+    StateContext, StateGraph, Timeout, poll_fn_raw, poll_fn_stream_raw, poll_fn_until_raw,
+    state::{EventChannel, Lagged, send_event_raw, subscribe_event_owned_raw, subscribe_event_raw},
+    subscribe_raw,
+    time::{AnyTime, TimerBackend},
+    update_raw,
+};
+
+/// A single-threaded counterpart of [`StateContainer`](crate::StateContainer) backed by `Rc<RefCell<..>>`.
+///
+/// The state type `St` does not need to be `Send` or `Sync`, so it can hold `Rc` handles and other thread-local values.
+/// The container and the futures and streams it returns are `!Send`, so they are meant for single-threaded executors.
+///
+/// Accessing the container from inside the closure of another access to the same container panics.
+#[derive(Ex)]
+#[derive_ex(Clone, bound())]
+pub struct LocalStateContainer<St>(Rc<RefCell<RawStateContainer<St>>>);
+
+impl<St> ContainerRef for LocalStateContainer<St> {
+    type St = St;
+    fn try_with<T>(
+        &self,
+        f: impl FnOnce(&mut StateGraph, &mut St) -> T,
+    ) -> Result<T, ContainerPoisoned> {
+        let ss = &mut *self.0.borrow_mut();
+        Ok(f(&mut ss.g, &mut ss.st))
+    }
+}
+
+impl<St> LocalStateContainer<St> {
+    pub fn new(f: impl FnOnce(&mut StateContext) -> St) -> Self {
+        Self(Rc::new(RefCell::new(RawStateContainer::new(f))))
+    }
+
+    pub async fn poll_fn<U>(&self, f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>) -> U {
+        poll_fn_raw(self, f).await
+    }
+
+    /// Like [`poll_fn`](Self::poll_fn), but gives up with [`Timeout`] when `deadline` is reached.
+    pub async fn poll_fn_until<U>(
+        &self,
+        deadline: impl Into<AnyTime>,
+        f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>,
+    ) -> Result<U, Timeout> {
+        poll_fn_until_raw(self, deadline.into(), f).await
+    }
+    pub fn poll_fn_stream<U>(
+        &self,
+        f: impl FnMut(&mut St, &mut StateContext) -> Poll<Option<U>> + 'static,
+    ) -> impl Stream<Item = U> + 'static
+    where
+        St: 'static,
+    {
+        poll_fn_stream_raw(self, f)
+    }
+    pub fn subscribe<U>(
+        &self,
+        f: impl FnMut(&mut St, &mut StateContext) -> U + 'static,
+    ) -> impl Stream<Item = U> + 'static
+    where
+        St: 'static,
+    {
+        subscribe_raw(self, f)
+    }
+    pub fn subscribe_event<T: Clone + 'static>(
+        &self,
+        channel: impl Fn(&mut St) -> &mut EventChannel<T> + 'static,
+    ) -> impl Stream<Item = T> + 'static
+    where
+        St: 'static,
+    {
+        self.subscribe_event_with(channel, |_st, _cx| [], |e| Some(e.clone()))
+    }
+    pub fn subscribe_event_with<T, U, I>(
+        &self,
+        channel: impl Fn(&mut St) -> &mut EventChannel<T> + 'static,
+        inits: impl FnOnce(&mut St, &mut StateContext) -> I + 'static,
+        filter_map: impl FnMut(&T) -> Option<U> + 'static,
+    ) -> impl Stream<Item = U> + 'static
+    where
+        St: 'static,
+        T: 'static,
+        U: 'static,
+        I: IntoIterator<Item = U>,
+    {
+        subscribe_event_raw(self, channel, inits, filter_map, |_| None)
+    }
+
+    /// Like [`subscribe_event`](Self::subscribe_event), but yields [`Lagged`] when events
+    /// were dropped by a bounded channel before this subscriber read them.
+    ///
+    /// See [`StateContainer::subscribe_event_lossy`](crate::StateContainer::subscribe_event_lossy).
+    pub fn subscribe_event_lossy<T: Clone + 'static>(
+        &self,
+        channel: impl Fn(&mut St) -> &mut EventChannel<T> + 'static,
+    ) -> impl Stream<Item = Result<T, Lagged>> + 'static
+    where
+        St: 'static,
+    {
+        subscribe_event_raw(
+            self,
+            channel,
+            |_st, _cx| [],
+            |e| Some(Ok(e.clone())),
+            |lagged| Some(Err(lagged)),
+        )
+    }
+
+    /// Subscribes to a [work queue](EventChannel::work_queue), taking each event by value.
+    ///
+    /// See [`StateContainer::subscribe_event_owned`](crate::StateContainer::subscribe_event_owned).
+    pub fn subscribe_event_owned<T: 'static>(
+        &self,
+        channel: impl Fn(&mut St) -> &mut EventChannel<T> + 'static,
+    ) -> impl Stream<Item = T> + 'static
+    where
+        St: 'static,
+    {
+        subscribe_event_owned_raw(self, channel)
+    }
+
+    /// Sends `value` to the channel returned by `channel`, waiting while the channel is full
+    /// if its policy is [`OverflowPolicy::Block`](crate::state::OverflowPolicy::Block).
+    pub async fn send_event<T>(&self, channel: impl Fn(&mut St) -> &mut EventChannel<T>, value: T) {
+        send_event_raw(self, channel, value).await
+    }
+
+    /// Sets the [`TimerBackend`] used for [`StateContext::notify_at`] and deadlines of this container.
+    pub fn set_timer_backend(&self, backend: impl TimerBackend + 'static) {
+        self.0.borrow_mut().g.set_timer_backend(backend);
+    }
+
+    /// Returns a snapshot of the live states and the targets that depend on them, for debugging.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        self.0.borrow_mut().g.dependency_graph()
+    }
+
+    /// Returns the runtime statistics of this container.
+    ///
+    /// The lock statistics are always zero, because this container is never shared between threads.
+    pub fn stats(&self) -> ContainerStats {
+        self.0.borrow_mut().g.stats()
+    }
+
+    pub fn lock_untracked(&self) -> Ref<'_, St> {
+        Ref::map(self.0.borrow(), |ss| &ss.st)
+    }
+
+    pub fn update<T>(&self, f: impl FnOnce(&mut St, &mut StateContext) -> T) -> T {
+        update_raw(self, f)
+    }
+
+    /// Updates the state with notifications deferred until `f` returns.
+    ///
+    /// See [`StateContainer::batch`](crate::StateContainer::batch).
+    pub fn batch<T>(&self, f: impl FnOnce(&mut St, &mut StateContext) -> T) -> T {
+        self.update(|st, cx| {
+            cx.defer_notifications();
+            f(st, cx)
+        })
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    future::poll_fn,
+    sync::{
+        Arc, LockResult, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+        TryLockError,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use derive_ex::Ex;
+use futures::{Stream, stream};
+
+use crate::{
+    ContainerPoisoned, ContainerStats, DependencyGraph, PoisonPolicy, StateContext, StateGraph,
+    Ticker, TryUpdateError,
+    time::{SpawnAtTask, TimerBackend},
+    utils::{Action, bipartite_graph::YKey, trace::trace_span},
+};
+
+/// A [`StateContainer`](crate::StateContainer) that lets tracking reads run concurrently.
+///
+/// [`poll_fn`](Self::poll_fn) and [`subscribe`](Self::subscribe) take a shared lock on the state,
+/// and record the states they read separately for each target,
+/// so that only [`update`](Self::update) takes an exclusive lock.
+///
+/// Because reads only get `&St`, states that need `&mut self` to read, such as [`Memo`](crate::state::Memo)
+/// and [`EventChannel`](crate::state::EventChannel), cannot be read through this container.
+/// Creating or notifying a [`StateKey`](crate::StateKey) while reading panics.
+#[derive(Ex)]
+#[derive_ex(Clone, bound())]
+pub struct RwStateContainer<St>(Arc<RawRwStateContainer<St>>);
+
+struct RawRwStateContainer<St> {
+    st: RwLock<St>,
+    g: Mutex<StateGraph>,
+    poison_policy: Mutex<PoisonPolicy>,
+}
+
+impl<St> RwStateContainer<St> {
+    pub fn new(f: impl FnOnce(&mut StateContext) -> St) -> Self {
+        let mut g = StateGraph::new();
+        let st = f(g.context());
+        Self(Arc::new(RawRwStateContainer {
+            st: RwLock::new(st),
+            g: Mutex::new(g),
+            poison_policy: Mutex::new(PoisonPolicy::default()),
+        }))
+    }
+
+    pub async fn poll_fn<U>(&self, mut f: impl FnMut(&St, &mut StateContext) -> Poll<U>) -> U {
+        let mut t = RwTarget::new(self);
+        poll_fn(|cx| t.poll_fn(&mut f, cx)).await
+    }
+
+    /// Like [`poll_fn`](Self::poll_fn), but returns [`ContainerPoisoned`] instead of panicking
+    /// if the container has been poisoned.
+    pub async fn try_poll_fn<U>(
+        &self,
+        mut f: impl FnMut(&St, &mut StateContext) -> Poll<U>,
+    ) -> Result<U, ContainerPoisoned> {
+        let mut t = RwTarget::new(self);
+        poll_fn(|cx| t.try_poll_fn(&mut f, cx)).await
+    }
+    pub fn poll_fn_stream<U>(
+        &self,
+        mut f: impl FnMut(&St, &mut StateContext) -> Poll<Option<U>> + 'static,
+    ) -> impl Stream<Item = U> + 'static
+    where
+        St: 'static,
+    {
+        let mut t = RwTarget::new(self);
+        stream::poll_fn(move |cx| t.poll_fn(&mut f, cx))
+    }
+
+    /// Returns a stream that yields the value returned by `f` each time the states read by `f` change.
+    ///
+    /// Unlike [`StateContainer::subscribe`](crate::StateContainer::subscribe), every re-run of `f` yields a value.
+    pub fn subscribe<U>(
+        &self,
+        mut f: impl FnMut(&St, &mut StateContext) -> U + 'static,
+    ) -> impl Stream<Item = U> + 'static
+    where
+        St: 'static,
+    {
+        struct WatchState {
+            waker: Option<Waker>,
+            age: usize,
+            is_dirty: bool,
+        }
+        fn wake(ws: Arc<Mutex<WatchState>>, age: usize) {
+            let mut ws = ws.lock().unwrap_or_else(PoisonError::into_inner);
+            if ws.age == age {
+                ws.is_dirty = true;
+                if let Some(waker) = ws.waker.take() {
+                    drop(ws);
+                    waker.wake();
+                }
+            }
+        }
+
+        let ws = Arc::new(Mutex::new(WatchState {
+            waker: None,
+            age: 0,
+            is_dirty: true,
+        }));
+        let mut t = RwTarget::new(self);
+        stream::poll_fn(move |cx| {
+            let mut w = ws.lock().unwrap_or_else(PoisonError::into_inner);
+            w.waker = Some(cx.waker().clone());
+            if !w.is_dirty {
+                return Poll::Pending;
+            }
+            w.is_dirty = false;
+            w.age = w.age.wrapping_add(1);
+            let age = w.age;
+            drop(w);
+            let _span = trace_span!("subscribe", target_id = t.key.map(|y| y.0));
+            let value = t
+                .run(
+                    &mut f,
+                    |_| true,
+                    || Action::from_arc_fn_usize(ws.clone(), wake, age),
+                )
+                .unwrap_or_else(|e| panic!("{e}"));
+            Poll::Ready(Some(value))
+        })
+    }
+
+    /// Sets the [`TimerBackend`] used for [`StateContext::notify_at`] of this container.
+    pub fn set_timer_backend(&self, backend: impl TimerBackend + 'static) {
+        self.graph().set_timer_backend(backend);
+    }
+
+    /// Sets how this container behaves after it has been poisoned by a panic in [`update`](Self::update).
+    /// The default is [`PoisonPolicy::Panic`].
+    ///
+    /// Setting [`PoisonPolicy::Recover`] on a container that is already poisoned recovers it on the next access.
+    pub fn set_poison_policy(&self, policy: PoisonPolicy) {
+        *self
+            .0
+            .poison_policy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = policy;
+    }
+
+    /// Returns a snapshot of the live states and the targets that depend on them, for debugging.
+    ///
+    /// This works even if the container has been poisoned.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        self.graph().dependency_graph()
+    }
+
+    /// Returns the runtime statistics of this container.
+    ///
+    /// The lock statistics only count the waits of [`update`](Self::update) for the exclusive lock.
+    /// This works even if the container has been poisoned.
+    pub fn stats(&self) -> ContainerStats {
+        self.graph().stats()
+    }
+
+    pub fn lock_untracked(&self) -> RwLockReadGuard<'_, St> {
+        self.check_poison(self.0.st.read())
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn update<T>(&self, f: impl FnOnce(&mut St, &mut StateContext) -> T) -> T {
+        let _span = trace_span!("update");
+        let mut wait = None;
+        let r = match self.0.st.try_write() {
+            Ok(st) => Ok(st),
+            Err(TryLockError::Poisoned(e)) => Err(e),
+            Err(TryLockError::WouldBlock) => {
+                let start = Instant::now();
+                let r = self.0.st.write();
+                wait = Some(start.elapsed());
+                r
+            }
+        };
+        let st = self.check_poison(r).unwrap_or_else(|e| panic!("{e}"));
+        self.update_locked(st, wait, f)
+    }
+
+    /// Like [`update`](Self::update), but returns an error without calling `f`
+    /// if the container is locked by someone else or has been poisoned.
+    pub fn try_update<T>(
+        &self,
+        f: impl FnOnce(&mut St, &mut StateContext) -> T,
+    ) -> Result<T, TryUpdateError> {
+        let _span = trace_span!("update");
+        let st = match self.0.st.try_write() {
+            Ok(st) => st,
+            Err(TryLockError::WouldBlock) => return Err(TryUpdateError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => self.check_poison(Err(e))?,
+        };
+        Ok(self.update_locked(st, None, f))
+    }
+
+    /// Updates the state with notifications deferred until the locks are released.
+    ///
+    /// See [`StateContainer::batch`](crate::StateContainer::batch).
+    pub fn batch<T>(&self, f: impl FnOnce(&mut St, &mut StateContext) -> T) -> T {
+        self.update(|st, cx| {
+            cx.defer_notifications();
+            f(st, cx)
+        })
+    }
+
+    fn update_locked<T>(
+        &self,
+        mut st: RwLockWriteGuard<'_, St>,
+        wait: Option<Duration>,
+        f: impl FnOnce(&mut St, &mut StateContext) -> T,
+    ) -> T {
+        let _wakes;
+        let mut g = self.graph();
+        if let Some(wait) = wait {
+            g.record_lock_wait(wait);
+        }
+        let value = f(&mut st, g.context());
+        _wakes = g.take_deferred_wakes();
+        value
+    }
+
+    /// Locks the dependency graph.
+    ///
+    /// Poisoning of this lock is ignored, because a panic in [`update`](Self::update) also poisons the state lock,
+    /// which decides whether the container is poisoned.
+    fn graph(&self) -> MutexGuard<'_, StateGraph> {
+        self.0.g.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    fn check_poison<G>(&self, r: LockResult<G>) -> Result<G, ContainerPoisoned> {
+        match r {
+            Ok(guard) => Ok(guard),
+            Err(e) => {
+                let policy = *self
+                    .0
+                    .poison_policy
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                if policy != PoisonPolicy::Recover {
+                    return Err(ContainerPoisoned);
+                }
+                self.graph().recover();
+                self.0.g.clear_poison();
+                self.0.st.clear_poison();
+                Ok(e.into_inner())
+            }
+        }
+    }
+}
+
+struct RwTarget<St> {
+    key: Option<YKey>,
+    st: RwStateContainer<St>,
+    sleep: Option<SpawnAtTask>,
+    ticker: Option<Ticker>,
+    recorded: StateGraph,
+}
+impl<St> RwTarget<St> {
+    fn new(st: &RwStateContainer<St>) -> Self {
+        Self {
+            key: None,
+            st: st.clone(),
+            sleep: None,
+            ticker: None,
+            recorded: StateGraph::new_read_only(),
+        }
+    }
+    /// Runs `f` under the shared lock, and arms `waker` if `is_waiting` returns `true` for the result.
+    fn run<T, A: Into<Action>>(
+        &mut self,
+        f: impl FnOnce(&St, &mut StateContext) -> T,
+        is_waiting: impl FnOnce(&T) -> bool,
+        waker: impl Fn() -> A,
+    ) -> Result<T, ContainerPoisoned> {
+        let st = self.st.check_poison(self.st.0.st.read())?;
+        self.recorded.clear_sources();
+        let value = f(&st, self.recorded.context());
+
+        // The target is committed before the shared lock is released, so that no update can be missed.
+        let mut g = self.st.graph();
+        if let Some(y) = self.key.take() {
+            g.remove_target(y);
+        }
+        self.sleep.take();
+        if is_waiting(&value) {
+            (self.key, self.sleep) = g.commit_recorded(&mut self.recorded, &mut self.ticker, waker);
+        }
+        Ok(value)
+    }
+    fn poll_fn<T>(
+        &mut self,
+        f: impl FnMut(&St, &mut StateContext) -> Poll<T>,
+        cx: &mut Context,
+    ) -> Poll<T> {
+        self.try_poll_fn(f, cx)
+            .map(|r| r.unwrap_or_else(|e| panic!("{e}")))
+    }
+    fn try_poll_fn<T>(
+        &mut self,
+        mut f: impl FnMut(&St, &mut StateContext) -> Poll<T>,
+        cx: &mut Context,
+    ) -> Poll<Result<T, ContainerPoisoned>> {
+        let _span = trace_span!("poll_fn", target_id = self.key.map(|y| y.0));
+        match self.run(&mut f, |value| value.is_pending(), || cx.waker()) {
+            Ok(value) => value.map(Ok),
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+impl<St> Drop for RwTarget<St> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.st.graph().remove_target(key);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::marker::PhantomData;
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{DeserializeSeed, IgnoredAny},
+};
+
+use crate::{StateContainer, StateContext};
+
+/// A type that is deserialized through a [`StateContext`], because it owns [`StateKey`](crate::StateKey)s.
+///
+/// State types such as [`Value`](crate::state::Value) serialize only their payload with [`Serialize`],
+/// and create new keys when deserialized with this trait.
+/// Every type that implements [`Deserialize`] implements this trait by ignoring the context.
+///
+/// To deserialize a field of a state type inside an implementation of this trait, use [`StateSeed`].
+pub trait DeserializeState<'de>: Sized {
+    fn deserialize_state<D: Deserializer<'de>>(
+        deserializer: D,
+        cx: &mut StateContext,
+    ) -> Result<Self, D::Error>;
+}
+
+impl<'de, T: Deserialize<'de>> DeserializeState<'de> for T {
+    fn deserialize_state<D: Deserializer<'de>>(
+        deserializer: D,
+        _cx: &mut StateContext,
+    ) -> Result<Self, D::Error> {
+        T::deserialize(deserializer)
+    }
+}
+
+/// A [`DeserializeSeed`] that deserializes `T` with [`DeserializeState`].
+pub struct StateSeed<'a, T> {
+    cx: &'a mut StateContext,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> StateSeed<'a, T> {
+    pub fn new(cx: &'a mut StateContext) -> Self {
+        Self {
+            cx,
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<'de, T: DeserializeState<'de>> DeserializeSeed<'de> for StateSeed<'_, T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        T::deserialize_state(deserializer, self.cx)
+    }
+}
+
+/// Deserializes the placeholder written by state types that have no payload to persist.
+pub(crate) fn deserialize_unit<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(), D::Error> {
+    IgnoredAny::deserialize(deserializer)?;
+    Ok(())
+}
+
+impl<St> StateContainer<St> {
+    /// Serializes the current state with `serializer`.
+    pub fn snapshot<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        St: Serialize,
+    {
+        self.lock_untracked().serialize(serializer)
+    }
+
+    /// Replaces the state with the one deserialized from `deserializer`, and notifies all dependents.
+    ///
+    /// If deserialization fails, the state is left unchanged and no dependents are notified.
+    pub fn restore<'de, D: Deserializer<'de>>(&self, deserializer: D) -> Result<(), D::Error>
+    where
+        St: DeserializeState<'de>,
+    {
+        self.update(|st, cx| {
+            *st = St::deserialize_state(deserializer, cx)?;
+            cx.notify_all();
+            Ok(())
+        })
+    }
+}
@@ -1,7 +1,25 @@
+mod bounded_queue;
+mod btree_map;
+mod element_mut;
 mod event_channel;
+mod hash_map;
+mod keyed_map;
+mod memo;
 mod queue;
+mod sender;
+mod state_init;
 mod value;
+mod vec;
 
-pub use event_channel::EventChannel;
+pub use bounded_queue::BoundedQueue;
+pub use btree_map::BTreeMap;
+pub use element_mut::ElementMut;
+pub use event_channel::{EventChannel, Lagged, OverflowPolicy, Replay};
+pub(crate) use event_channel::{send_event_raw, subscribe_event_owned_raw, subscribe_event_raw};
+pub use hash_map::HashMap;
+pub use memo::Memo;
 pub use queue::*;
-pub use value::Value;
+pub use sender::{EventSender, QueueSender};
+pub use state_init::StateInit;
+pub use value::{Value, ValueMut};
+pub use vec::Vec;
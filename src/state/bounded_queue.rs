@@ -0,0 +1,182 @@
+use std::{collections::VecDeque, task::Poll};
+
+use crate::{
+    ContainerRef, LocalStateContainer, StateContainer, StateContext, StateKey, poll_fn_raw,
+    state::StateInit,
+};
+
+/// A queue with a maximum number of items, for use in state type `St` of [`StateContainer<St>`](crate::StateContainer).
+///
+/// Like [`Queue`](crate::state::Queue), popping from an empty queue registers the queue as a dependency,
+/// and pushing to an empty queue notifies its dependents.
+/// In addition, pushing to a full queue registers a dependency on the space in the queue,
+/// and popping from a full queue notifies the producers waiting for space.
+#[derive(Debug)]
+pub struct BoundedQueue<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+    key: StateKey,
+    space_key: StateKey,
+    len: StateKey,
+}
+impl<T> BoundedQueue<T> {
+    /// Creates a new empty queue that holds at most `capacity` items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize, cx: &mut StateContext) -> Self {
+        assert!(capacity != 0, "`capacity` must be non-zero");
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+            key: StateKey::new(cx),
+            space_key: StateKey::new(cx),
+            len: StateKey::new(cx),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn len(&self, cx: &mut StateContext) -> usize {
+        self.len.watch(cx);
+        self.items.len()
+    }
+    pub fn is_empty(&self, cx: &mut StateContext) -> bool {
+        self.len(cx) == 0
+    }
+    pub fn is_full(&self, cx: &mut StateContext) -> bool {
+        self.len(cx) >= self.capacity
+    }
+    fn is_full_untracked(&self) -> bool {
+        self.items.len() >= self.capacity
+    }
+
+    /// Adds an item to the queue, or returns it back if the queue is full.
+    ///
+    /// If the queue was empty before pushing, notifies dependents that the state has changed.
+    /// Unlike [`push`](Self::push), this does not register a dependency when the queue is full.
+    pub fn try_push(&mut self, item: T, cx: &mut StateContext) -> Result<(), T> {
+        if self.is_full_untracked() {
+            return Err(item);
+        }
+        if self.items.is_empty() {
+            self.key.notify(cx);
+        }
+        self.items.push_back(item);
+        self.len.notify(cx);
+        Ok(())
+    }
+
+    /// Moves the item out of `item` into the queue if the queue has space.
+    ///
+    /// If the queue is full, leaves `item` as it is, registers a dependency that is notified
+    /// when an item is popped, and returns `Poll::Pending`.
+    /// Returns `Poll::Ready` immediately if `item` is `None`.
+    ///
+    /// Taking `&mut Option<T>` lets the item be kept across re-runs of a [`poll_fn`](crate::StateContainer::poll_fn) closure.
+    pub fn push(&mut self, item: &mut Option<T>, cx: &mut StateContext) -> Poll<()> {
+        let Some(value) = item.take() else {
+            return Poll::Ready(());
+        };
+        match self.try_push(value, cx) {
+            Ok(()) => Poll::Ready(()),
+            Err(value) => {
+                *item = Some(value);
+                self.space_key.watch(cx);
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Removes the first item from the queue.
+    ///
+    /// If the queue was full before popping, notifies the producers waiting for space.
+    pub fn pop(&mut self, cx: &mut StateContext) -> Poll<T> {
+        let is_full = self.is_full_untracked();
+        match self.items.pop_front() {
+            Some(item) => {
+                self.len.notify(cx);
+                if is_full {
+                    self.space_key.notify(cx);
+                }
+                Poll::Ready(item)
+            }
+            None => {
+                self.key.watch(cx);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> StateInit for BoundedQueue<T> {
+    /// The capacity of the queue.
+    type Init = usize;
+
+    fn init(capacity: usize, cx: &mut StateContext) -> Self {
+        Self::new(capacity, cx)
+    }
+}
+
+/// A queue is serialized with its capacity and items.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for BoundedQueue<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("BoundedQueue", 2)?;
+        s.serialize_field("capacity", &self.capacity)?;
+        s.serialize_field("items", &self.items)?;
+        s.end()
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> crate::DeserializeState<'de> for BoundedQueue<T> {
+    fn deserialize_state<D: serde::Deserializer<'de>>(
+        deserializer: D,
+        cx: &mut StateContext,
+    ) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "BoundedQueue")]
+        struct Repr<T> {
+            capacity: usize,
+            items: VecDeque<T>,
+        }
+        let Repr { capacity, items } = serde::Deserialize::deserialize(deserializer)?;
+        if capacity == 0 {
+            return Err(serde::de::Error::custom("`capacity` must be non-zero"));
+        }
+        if items.len() > capacity {
+            return Err(serde::de::Error::custom(format_args!(
+                "{} items do not fit in a queue with capacity {capacity}",
+                items.len()
+            )));
+        }
+        let mut queue = Self::new(capacity, cx);
+        queue.items = items;
+        Ok(queue)
+    }
+}
+
+impl<St> StateContainer<St> {
+    /// Pushes `item` to the queue returned by `queue`, waiting until the queue has space.
+    pub async fn push_bounded<T>(&self, queue: impl Fn(&mut St) -> &mut BoundedQueue<T>, item: T) {
+        push_bounded_raw(self, queue, item).await
+    }
+}
+impl<St> LocalStateContainer<St> {
+    /// Pushes `item` to the queue returned by `queue`, waiting until the queue has space.
+    pub async fn push_bounded<T>(&self, queue: impl Fn(&mut St) -> &mut BoundedQueue<T>, item: T) {
+        push_bounded_raw(self, queue, item).await
+    }
+}
+
+async fn push_bounded_raw<C: ContainerRef, T>(
+    c: &C,
+    queue: impl Fn(&mut C::St) -> &mut BoundedQueue<T>,
+    item: T,
+) {
+    let mut item = Some(item);
+    poll_fn_raw(c, |st, cx| queue(st).push(&mut item, cx)).await
+}
@@ -0,0 +1,10 @@
+use crate::state::keyed_map::keyed_map;
+
+keyed_map! {
+    /// An ordered map for use in state type `St` of [`StateContainer<St>`](crate::StateContainer) that tracks dependencies per key.
+    ///
+    /// Each entry has its own [`StateKey`](crate::StateKey), so reading one entry only depends on that entry.
+    /// Reading the length depends on the set of keys, and iterating depends on the whole map.
+    /// Looking up a missing key depends on the set of keys, because the key may be inserted later.
+    BTreeMap(BTreeMap, btree_map): Ord
+}
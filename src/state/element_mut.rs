@@ -0,0 +1,53 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{StateContext, StateKey};
+
+/// Mutable access to an element of a [`Vec`](crate::state::Vec), [`HashMap`](crate::state::HashMap)
+/// or [`BTreeMap`](crate::state::BTreeMap), returned by their `get_mut`.
+///
+/// Like [`ValueMut`](crate::state::ValueMut), dependents of the element and of the whole collection are notified when the guard is dropped,
+/// but only if the element was accessed mutably through it.
+pub struct ElementMut<'a, T> {
+    value: &'a mut T,
+    key: &'a StateKey,
+    all: &'a StateKey,
+    cx: &'a mut StateContext,
+    is_modified: bool,
+}
+impl<'a, T> ElementMut<'a, T> {
+    pub(crate) fn new(
+        value: &'a mut T,
+        key: &'a StateKey,
+        all: &'a StateKey,
+        cx: &'a mut StateContext,
+    ) -> Self {
+        key.watch(cx);
+        Self {
+            value,
+            key,
+            all,
+            cx,
+            is_modified: false,
+        }
+    }
+}
+impl<T> Deref for ElementMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+impl<T> DerefMut for ElementMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.is_modified = true;
+        self.value
+    }
+}
+impl<T> Drop for ElementMut<'_, T> {
+    fn drop(&mut self) {
+        if self.is_modified {
+            self.key.notify(self.cx);
+            self.all.notify(self.cx);
+        }
+    }
+}
@@ -1,32 +1,299 @@
-use std::{collections::VecDeque, task::Poll};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
 
 use futures::Stream;
 
 use crate::{
-    StateContainer, StateContext, StateKey,
+    ContainerRef, StateContainer, StateContext, StateKey, poll_fn_raw, poll_fn_stream_raw,
+    state::StateInit,
+    update_raw,
     utils::shared_queue::{SharedQueue, SharedQueueCursor},
 };
 
+/// A channel of transient events, for use in state type `St` of [`StateContainer<St>`](crate::StateContainer).
+///
+/// By default, every subscriber receives every event sent after it subscribed.
+/// [`subscribe_event`](crate::StateContainer::subscribe_event) clones each event for each subscriber,
+/// so for large payloads use `EventChannel<Arc<T>>` with [`send_shared`](Self::send_shared) to share them instead.
+///
+/// A channel created by [`work_queue`](Self::work_queue) delivers each event to exactly one of its subscribers,
+/// which can take it by value with [`subscribe_event_owned`](crate::StateContainer::subscribe_event_owned).
 pub struct EventChannel<T> {
     queue: SharedQueue<T>,
+    work_cursor: Option<SharedQueueCursor<T>>,
     key: StateKey,
+    space_key: StateKey,
+    capacity: Option<(usize, OverflowPolicy)>,
+    replay: Option<Replay>,
+    sent_at: VecDeque<Instant>,
+    id: u64,
+}
+
+/// Which past events an [`EventChannel`] replays to new subscribers. See [`EventChannel::set_replay`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Replay {
+    /// Replay the last `n` events.
+    Last(usize),
+    /// Replay the events sent within the duration before subscribing, measured with [`time::now`](crate::time::now).
+    Within(Duration),
+}
+
+/// What a bounded [`EventChannel`] does when an event is sent while the slowest subscriber has `capacity` unread events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverflowPolicy {
+    /// Drop the oldest event. Subscribers that had not read it skip it and are told how many events they missed.
+    DropOldest,
+    /// Make senders wait with [`EventChannel::poll_send`] until every subscriber has read the oldest event.
+    Block,
+    /// Drop the oldest event like [`DropOldest`](Self::DropOldest), and end the streams of subscribers that had not read it.
+    Disconnect,
 }
 
+/// The error yielded by [`StateContainer::subscribe_event_lossy`] when events were dropped before the subscriber read them.
+///
+/// The value is the number of events missed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lagged(pub u64);
+
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "subscriber lagged behind by {} events", self.0)
+    }
+}
+impl std::error::Error for Lagged {}
+
 impl<T> EventChannel<T> {
     pub fn new(cx: &mut StateContext) -> Self {
+        Self::new_raw(None, false, cx)
+    }
+
+    /// Creates a channel that delivers each event to exactly one of its subscribers.
+    ///
+    /// Subscribers compete for events, and events sent while there are no subscribers are kept
+    /// until a subscriber takes them. Events dropped by the filter of
+    /// [`subscribe_event_with`](crate::StateContainer::subscribe_event_with) are not delivered to the other subscribers.
+    /// [Replay](Self::set_replay) has no effect on a work queue.
+    pub fn work_queue(cx: &mut StateContext) -> Self {
+        Self::new_raw(None, true, cx)
+    }
+
+    /// Creates a [work queue](Self::work_queue) that keeps at most `capacity` events that no subscriber has taken.
+    ///
+    /// With [`OverflowPolicy::Disconnect`], the oldest event is dropped as with [`OverflowPolicy::DropOldest`].
+    /// Dropped events are not reported as [`Lagged`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn bounded_work_queue(
+        capacity: usize,
+        policy: OverflowPolicy,
+        cx: &mut StateContext,
+    ) -> Self {
+        assert!(capacity != 0, "`capacity` must be non-zero");
+        Self::new_raw(Some((capacity, policy)), true, cx)
+    }
+
+    /// Creates a channel that keeps at most `capacity` events unread by its slowest subscriber.
+    ///
+    /// `policy` decides what happens when another event is sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn bounded(capacity: usize, policy: OverflowPolicy, cx: &mut StateContext) -> Self {
+        assert!(capacity != 0, "`capacity` must be non-zero");
+        Self::new_raw(Some((capacity, policy)), false, cx)
+    }
+    fn new_raw(
+        capacity: Option<(usize, OverflowPolicy)>,
+        is_work_queue: bool,
+        cx: &mut StateContext,
+    ) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let mut queue = SharedQueue::new();
+        // The subscribers of a work queue share one cursor, which keeps events until one of them takes them.
+        let work_cursor = is_work_queue.then(|| queue.create_cursor());
         Self {
-            queue: SharedQueue::new(),
+            queue,
+            work_cursor,
             key: StateKey::new(cx),
+            space_key: StateKey::new(cx),
+            capacity,
+            replay: None,
+            sent_at: VecDeque::new(),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Returns `true` if the channel was created by [`work_queue`](Self::work_queue) or [`bounded_work_queue`](Self::bounded_work_queue).
+    pub fn is_work_queue(&self) -> bool {
+        self.work_cursor.is_some()
+    }
+
+    /// Returns `true` if the channel is bounded and its slowest subscriber has `capacity` unread events.
+    pub fn is_full(&self) -> bool {
+        self.capacity
+            .is_some_and(|(capacity, _)| self.queue.unread_len() >= capacity)
+    }
+
+    /// Sets which past events are replayed to new subscribers, or disables replay with `None`.
+    ///
+    /// Only events sent after replay is enabled are kept for it.
+    /// The kept events count toward the capacity of a bounded channel, so with [`OverflowPolicy::DropOldest`]
+    /// and [`OverflowPolicy::Disconnect`] at most `capacity` events are replayed.
+    pub fn set_replay(&mut self, replay: Option<Replay>) {
+        self.replay = replay;
+        if !matches!(replay, Some(Replay::Within(_))) {
+            self.sent_at.clear();
         }
+        self.expire_replay();
     }
+
+    /// Sends an event to the current subscribers.
+    ///
+    /// With [`OverflowPolicy::Block`], the event is kept even if the channel is full.
+    /// Use [`try_send`](Self::try_send) or [`poll_send`](Self::poll_send) to respect the capacity.
     pub fn send(&mut self, value: T, cx: &mut StateContext) {
-        self.queue.push(value);
-        self.key.notify(cx);
+        self.push_with(
+            |queue| {
+                queue.push(value);
+                1
+            },
+            cx,
+        );
     }
     pub fn send_all(&mut self, values: impl IntoIterator<Item = T>, cx: &mut StateContext) {
-        self.queue.extend(values);
+        self.push_with(
+            |queue| {
+                let mut n = 0;
+                queue.extend(values.into_iter().inspect(|_| n += 1));
+                n
+            },
+            cx,
+        );
+    }
+
+    /// Sends an event, or returns it back if the channel is full and its policy is [`OverflowPolicy::Block`].
+    pub fn try_send(&mut self, value: T, cx: &mut StateContext) -> Result<(), T> {
+        if self.is_full() && self.policy() == Some(OverflowPolicy::Block) {
+            return Err(value);
+        }
+        self.send(value, cx);
+        Ok(())
+    }
+
+    /// Moves the event out of `value` and sends it, unless the channel is full and its policy is [`OverflowPolicy::Block`].
+    ///
+    /// In that case, leaves `value` as it is, registers a dependency that is notified
+    /// when the subscribers have read enough events, and returns `Poll::Pending`.
+    /// Returns `Poll::Ready` immediately if `value` is `None`.
+    pub fn poll_send(&mut self, value: &mut Option<T>, cx: &mut StateContext) -> Poll<()> {
+        let Some(v) = value.take() else {
+            return Poll::Ready(());
+        };
+        match self.try_send(v, cx) {
+            Ok(()) => Poll::Ready(()),
+            Err(v) => {
+                *value = Some(v);
+                self.space_key.watch(cx);
+                Poll::Pending
+            }
+        }
+    }
+
+    fn policy(&self) -> Option<OverflowPolicy> {
+        self.capacity.map(|(_, policy)| policy)
+    }
+    /// Pushes events with `push`, which returns the number of events pushed, and notifies subscribers.
+    fn push_with(
+        &mut self,
+        push: impl FnOnce(&mut SharedQueue<T>) -> usize,
+        cx: &mut StateContext,
+    ) {
+        let is_timed = matches!(self.replay, Some(Replay::Within(_)));
+        if is_timed {
+            // Keep every event until its send time is recorded.
+            self.queue.set_retain(usize::MAX);
+        }
+        let n = push(&mut self.queue);
+        if is_timed {
+            let now = crate::time::now();
+            self.sent_at.extend((0..n).map(|_| now));
+        }
+        self.drop_overflow();
+        self.expire_replay();
         self.key.notify(cx);
     }
+
+    /// Drops the send times of events that are no longer replayed, and lets the queue release those events.
+    fn expire_replay(&mut self) {
+        let retain = match self.replay {
+            _ if self.is_work_queue() => 0,
+            None => 0,
+            Some(Replay::Last(n)) => n,
+            Some(Replay::Within(d)) => {
+                if let Some(since) = crate::time::now().checked_sub(d) {
+                    while self.sent_at.front().is_some_and(|&at| at < since) {
+                        self.sent_at.pop_front();
+                    }
+                }
+                while self.sent_at.len() > self.queue.len() {
+                    self.sent_at.pop_front();
+                }
+                self.sent_at.len()
+            }
+        };
+        self.queue.set_retain(retain);
+    }
+    /// Creates the cursor of a new subscriber, or returns `None` for a work queue, whose subscribers share one cursor.
+    fn create_cursor(&mut self) -> Option<SharedQueueCursor<T>> {
+        if self.is_work_queue() {
+            return None;
+        }
+        self.expire_replay();
+        Some(self.queue.create_cursor())
+    }
+    /// Takes the oldest event of a work queue.
+    fn take(&mut self) -> Option<T> {
+        self.queue.take(self.work_cursor.as_mut()?)
+    }
+    fn drop_overflow(&mut self) {
+        if let Some((capacity, policy)) = self.capacity {
+            if policy != OverflowPolicy::Block {
+                while self.queue.len() > capacity {
+                    self.queue.pop_oldest();
+                }
+            }
+        }
+    }
+
+    /// Runs `f`, which may let the slowest subscriber catch up, and notifies waiting senders if it made room.
+    fn release<U>(&mut self, cx: &mut StateContext, f: impl FnOnce(&mut Self) -> U) -> U {
+        let is_full = self.is_full();
+        let value = f(self);
+        if is_full && !self.is_full() {
+            self.space_key.notify(cx);
+        }
+        value
+    }
+}
+
+impl<T> EventChannel<Arc<T>> {
+    /// Sends an event that subscribers share instead of cloning.
+    pub fn send_shared(&mut self, value: T, cx: &mut StateContext) {
+        self.send(Arc::new(value), cx);
+    }
 }
 
 impl<St> StateContainer<St> {
@@ -43,7 +310,7 @@ impl<St> StateContainer<St> {
         &self,
         channel: impl Fn(&mut St) -> &mut EventChannel<T> + 'static,
         inits: impl FnOnce(&mut St, &mut StateContext) -> I + 'static,
-        mut filter_map: impl FnMut(&T) -> Option<U> + 'static,
+        filter_map: impl FnMut(&T) -> Option<U> + 'static,
     ) -> impl Stream<Item = U> + 'static
     where
         St: 'static,
@@ -51,53 +318,294 @@ impl<St> StateContainer<St> {
         U: 'static,
         I: IntoIterator<Item = U>,
     {
-        let (mut items, cursor) = self.update(|st, cx| {
-            (
-                inits(st, cx).into_iter().collect::<VecDeque<_>>(),
-                channel(st).queue.create_cursor(),
-            )
-        });
-        let mut s = Scope {
-            st: self.clone(),
+        subscribe_event_raw(self, channel, inits, filter_map, |_| None)
+    }
+
+    /// Like [`subscribe_event`](Self::subscribe_event), but yields [`Lagged`] when events
+    /// were dropped by a bounded channel before this subscriber read them.
+    ///
+    /// With [`OverflowPolicy::Disconnect`], the stream ends after yielding [`Lagged`].
+    pub fn subscribe_event_lossy<T: Clone + 'static>(
+        &self,
+        channel: impl Fn(&mut St) -> &mut EventChannel<T> + 'static,
+    ) -> impl Stream<Item = Result<T, Lagged>> + 'static
+    where
+        St: 'static,
+    {
+        subscribe_event_raw(
+            self,
             channel,
-            cursor: Some(cursor),
-        };
-        self.poll_fn_stream(move |st, cx| {
-            if items.is_empty() {
-                items.extend(
-                    (s.channel)(st)
-                        .queue
-                        .read(s.cursor.as_mut().unwrap())
-                        .iter()
-                        .filter_map(&mut filter_map),
-                );
+            |_st, _cx| [],
+            |e| Some(Ok(e.clone())),
+            |lagged| Some(Err(lagged)),
+        )
+    }
+
+    /// Subscribes to a [work queue](EventChannel::work_queue), taking each event by value.
+    ///
+    /// Each event is yielded by only one of the streams subscribed to the channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is not a work queue.
+    pub fn subscribe_event_owned<T: 'static>(
+        &self,
+        channel: impl Fn(&mut St) -> &mut EventChannel<T> + 'static,
+    ) -> impl Stream<Item = T> + 'static
+    where
+        St: 'static,
+    {
+        subscribe_event_owned_raw(self, channel)
+    }
+
+    /// Sends `value` to the channel returned by `channel`, waiting while the channel is full
+    /// if its policy is [`OverflowPolicy::Block`].
+    pub async fn send_event<T>(&self, channel: impl Fn(&mut St) -> &mut EventChannel<T>, value: T) {
+        send_event_raw(self, channel, value).await
+    }
+}
+
+/// An event passed to a subscriber, borrowed from a broadcast channel or taken from a work queue.
+enum Delivery<'a, T> {
+    Shared(&'a T),
+    Owned(T),
+}
+impl<T> Delivery<'_, T> {
+    fn as_ref(&self) -> &T {
+        match self {
+            Delivery::Shared(value) => value,
+            Delivery::Owned(value) => value,
+        }
+    }
+}
+
+pub(crate) async fn send_event_raw<C: ContainerRef, T>(
+    c: &C,
+    channel: impl Fn(&mut C::St) -> &mut EventChannel<T>,
+    value: T,
+) {
+    let mut value = Some(value);
+    poll_fn_raw(c, |st, cx| channel(st).poll_send(&mut value, cx)).await
+}
+
+pub(crate) fn subscribe_event_raw<C, T, U, I>(
+    c: &C,
+    channel: impl Fn(&mut C::St) -> &mut EventChannel<T> + 'static,
+    inits: impl FnOnce(&mut C::St, &mut StateContext) -> I + 'static,
+    mut filter_map: impl FnMut(&T) -> Option<U> + 'static,
+    on_lagged: impl FnMut(Lagged) -> Option<U> + 'static,
+) -> impl Stream<Item = U> + 'static
+where
+    C: ContainerRef + 'static,
+    T: 'static,
+    U: 'static,
+    I: IntoIterator<Item = U>,
+{
+    subscribe_delivery_raw(
+        c,
+        channel,
+        inits,
+        move |e| filter_map(e.as_ref()),
+        on_lagged,
+        false,
+    )
+}
+
+pub(crate) fn subscribe_event_owned_raw<C, T>(
+    c: &C,
+    channel: impl Fn(&mut C::St) -> &mut EventChannel<T> + 'static,
+) -> impl Stream<Item = T> + 'static
+where
+    C: ContainerRef + 'static,
+    T: 'static,
+{
+    // Panic after the lock is released so that the container is not poisoned.
+    let is_work_queue = c.with(|_, st| channel(st).is_work_queue());
+    assert!(
+        is_work_queue,
+        "`subscribe_event_owned` requires a work queue"
+    );
+    subscribe_delivery_raw(
+        c,
+        channel,
+        |_st, _cx| [],
+        |e| match e {
+            Delivery::Owned(value) => Some(value),
+            // The stream ends before reading a channel that is not a work queue.
+            Delivery::Shared(_) => None,
+        },
+        |_| None,
+        true,
+    )
+}
+
+fn subscribe_delivery_raw<C, T, U, I>(
+    c: &C,
+    channel: impl Fn(&mut C::St) -> &mut EventChannel<T> + 'static,
+    inits: impl FnOnce(&mut C::St, &mut StateContext) -> I + 'static,
+    mut filter_map: impl FnMut(Delivery<T>) -> Option<U> + 'static,
+    mut on_lagged: impl FnMut(Lagged) -> Option<U> + 'static,
+    requires_work_queue: bool,
+) -> impl Stream<Item = U> + 'static
+where
+    C: ContainerRef + 'static,
+    T: 'static,
+    U: 'static,
+    I: IntoIterator<Item = U>,
+{
+    let (mut items, cursor, channel_id) = update_raw(c, |st, cx| {
+        let items = inits(st, cx).into_iter().collect::<VecDeque<_>>();
+        let channel = channel(st);
+        (items, channel.create_cursor(), channel.id)
+    });
+    let mut s = Scope {
+        st: c.clone(),
+        channel,
+        cursor,
+        channel_id,
+    };
+    let mut is_disconnected = false;
+    poll_fn_stream_raw(c, move |st, cx| {
+        if is_disconnected {
+            // Disconnected because of lagging, or because the channel was replaced by one that is not a work queue.
+            return Poll::Ready(items.pop_front());
+        }
+        let channel = (s.channel)(st);
+        if channel.id != s.channel_id {
+            // The channel has been replaced, for example by a restore, so continue with the new one.
+            if requires_work_queue && !channel.is_work_queue() {
+                is_disconnected = true;
+                return Poll::Ready(items.pop_front());
             }
-            if let Some(item) = items.pop_front() {
-                Poll::Ready(Some(item))
+            s.cursor = channel.create_cursor();
+            s.channel_id = channel.id;
+        }
+        if items.is_empty() {
+            if let Some(cursor) = &mut s.cursor {
+                is_disconnected = channel.release(cx, |channel| {
+                    let mut reader = channel.queue.read(cursor);
+                    let lagged = reader.lagged();
+                    if lagged != 0 {
+                        items.extend(on_lagged(Lagged(lagged as u64)));
+                        if channel
+                            .capacity
+                            .is_some_and(|(_, policy)| policy == OverflowPolicy::Disconnect)
+                        {
+                            return true;
+                        }
+                    }
+                    items.extend(
+                        reader
+                            .iter()
+                            .filter_map(|value| filter_map(Delivery::Shared(value))),
+                    );
+                    false
+                });
+                if is_disconnected {
+                    let cursor = s.cursor.take().unwrap();
+                    channel.release(cx, |channel| channel.queue.drop_cursor(cursor));
+                    return Poll::Ready(items.pop_front());
+                }
             } else {
-                (s.channel)(st).key.watch(cx);
-                Poll::Pending
+                // Take one event at a time so that the other subscribers of the work queue can take the rest.
+                while items.is_empty() {
+                    let Some(value) = channel.release(cx, |channel| channel.take()) else {
+                        break;
+                    };
+                    items.extend(filter_map(Delivery::Owned(value)));
+                }
             }
-        })
-    }
+        }
+        if let Some(item) = items.pop_front() {
+            Poll::Ready(Some(item))
+        } else {
+            channel.key.watch(cx);
+            Poll::Pending
+        }
+    })
 }
-struct Scope<St, T, ToChannel>
+struct Scope<C, T, ToChannel>
 where
-    ToChannel: Fn(&mut St) -> &mut EventChannel<T>,
+    C: ContainerRef,
+    ToChannel: Fn(&mut C::St) -> &mut EventChannel<T>,
 {
-    st: StateContainer<St>,
+    st: C,
     channel: ToChannel,
     cursor: Option<SharedQueueCursor<T>>,
+    channel_id: u64,
 }
-impl<St, T, ToChannel> Drop for Scope<St, T, ToChannel>
+impl<C, T, ToChannel> Drop for Scope<C, T, ToChannel>
 where
-    ToChannel: Fn(&mut St) -> &mut EventChannel<T>,
+    C: ContainerRef,
+    ToChannel: Fn(&mut C::St) -> &mut EventChannel<T>,
 {
     fn drop(&mut self) {
         if let Some(cursor) = self.cursor.take() {
-            self.st.update(|st, _cx| {
-                (self.channel)(st).queue.drop_cursor(cursor);
-            });
+            // Ignore poisoning so that dropping the stream while unwinding does not panic again.
+            self.st
+                .try_with(|g, st| {
+                    let channel = (self.channel)(st);
+                    if channel.id == self.channel_id {
+                        channel.release(g.context(), |channel| channel.queue.drop_cursor(cursor));
+                    }
+                })
+                .ok();
+        }
+    }
+}
+
+impl<T> StateInit for EventChannel<T> {
+    type Init = ();
+
+    fn init((): (), cx: &mut StateContext) -> Self {
+        Self::new(cx)
+    }
+}
+
+/// The configuration of a channel, which is what a snapshot keeps of it.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ChannelConfig {
+    #[serde(default)]
+    capacity: Option<(usize, OverflowPolicy)>,
+    #[serde(default)]
+    replay: Option<Replay>,
+    #[serde(default)]
+    is_work_queue: bool,
+}
+
+/// Events and their send times are transient, so a channel is serialized as its configuration
+/// and deserialized as an empty channel with it.
+///
+/// A unit is deserialized as an unbounded channel.
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for EventChannel<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ChannelConfig {
+            capacity: self.capacity,
+            replay: self.replay,
+            is_work_queue: self.is_work_queue(),
+        }
+        .serialize(serializer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, T> crate::DeserializeState<'de> for EventChannel<T> {
+    fn deserialize_state<D: serde::Deserializer<'de>>(
+        deserializer: D,
+        cx: &mut StateContext,
+    ) -> Result<Self, D::Error> {
+        let config: Option<ChannelConfig> = serde::Deserialize::deserialize(deserializer)?;
+        let config = config.unwrap_or(ChannelConfig {
+            capacity: None,
+            replay: None,
+            is_work_queue: false,
+        });
+        if let Some((0, _)) = config.capacity {
+            return Err(serde::de::Error::custom("`capacity` must be non-zero"));
         }
+        let mut channel = Self::new_raw(config.capacity, config.is_work_queue, cx);
+        channel.set_replay(config.replay);
+        Ok(channel)
     }
 }
@@ -0,0 +1,213 @@
+//! The implementation shared by [`HashMap`](crate::state::HashMap) and [`BTreeMap`](crate::state::BTreeMap).
+
+/// Defines a map that tracks dependencies per key, backed by a map of `std::collections`.
+///
+/// `$std` is the backing map type, `$entry` the module of its `Entry` type,
+/// and `$bound` the bounds that the backing map requires of keys and lookup keys.
+macro_rules! keyed_map {
+    (
+        $(#[$attr:meta])*
+        $name:ident($std:ident, $entry:ident): $($bound:tt)+
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        pub struct $name<K, V> {
+            entries: ::std::collections::$std<K, Entry<V>>,
+            keys: $crate::StateKey,
+            all: $crate::StateKey,
+        }
+
+        #[derive(Debug)]
+        struct Entry<V> {
+            value: V,
+            key: $crate::StateKey,
+        }
+
+        impl<K, V> $name<K, V>
+        where
+            K: $($bound)+,
+        {
+            /// Creates a new empty map.
+            pub fn new(cx: &mut $crate::StateContext) -> Self {
+                Self {
+                    entries: ::std::collections::$std::new(),
+                    keys: $crate::StateKey::new(cx),
+                    all: $crate::StateKey::new(cx),
+                }
+            }
+            fn from_entries(
+                entries: ::std::collections::$std<K, V>,
+                cx: &mut $crate::StateContext,
+            ) -> Self {
+                Self {
+                    entries: entries
+                        .into_iter()
+                        .map(|(k, value)| {
+                            let key = $crate::StateKey::new(cx);
+                            (k, Entry { value, key })
+                        })
+                        .collect(),
+                    keys: $crate::StateKey::new(cx),
+                    all: $crate::StateKey::new(cx),
+                }
+            }
+
+            pub fn get<Q>(&self, key: &Q, cx: &mut $crate::StateContext) -> Option<&V>
+            where
+                K: ::std::borrow::Borrow<Q>,
+                Q: $($bound)+ + ?Sized,
+            {
+                match self.entries.get(key) {
+                    Some(e) => {
+                        e.key.watch(cx);
+                        Some(&e.value)
+                    }
+                    None => {
+                        self.keys.watch(cx);
+                        None
+                    }
+                }
+            }
+            pub fn get_untracked<Q>(&self, key: &Q) -> Option<&V>
+            where
+                K: ::std::borrow::Borrow<Q>,
+                Q: $($bound)+ + ?Sized,
+            {
+                Some(&self.entries.get(key)?.value)
+            }
+
+            /// Returns a guard that gives mutable access to the value.
+            ///
+            /// Dependents of the entry and of the whole map are notified when the guard is dropped,
+            /// but only if the value was accessed mutably through it.
+            pub fn get_mut<'a, Q>(
+                &'a mut self,
+                key: &Q,
+                cx: &'a mut $crate::StateContext,
+            ) -> Option<$crate::state::ElementMut<'a, V>>
+            where
+                K: ::std::borrow::Borrow<Q>,
+                Q: $($bound)+ + ?Sized,
+            {
+                match self.entries.get_mut(key) {
+                    Some(e) => Some($crate::state::ElementMut::new(
+                        &mut e.value,
+                        &e.key,
+                        &self.all,
+                        cx,
+                    )),
+                    None => {
+                        self.keys.watch(cx);
+                        None
+                    }
+                }
+            }
+            pub fn contains_key<Q>(&self, key: &Q, cx: &mut $crate::StateContext) -> bool
+            where
+                K: ::std::borrow::Borrow<Q>,
+                Q: $($bound)+ + ?Sized,
+            {
+                match self.entries.get(key) {
+                    Some(e) => {
+                        e.key.watch(cx);
+                        true
+                    }
+                    None => {
+                        self.keys.watch(cx);
+                        false
+                    }
+                }
+            }
+            pub fn len(&self, cx: &mut $crate::StateContext) -> usize {
+                self.keys.watch(cx);
+                self.entries.len()
+            }
+            pub fn is_empty(&self, cx: &mut $crate::StateContext) -> bool {
+                self.len(cx) == 0
+            }
+            pub fn iter(&self, cx: &mut $crate::StateContext) -> impl Iterator<Item = (&K, &V)> {
+                self.all.watch(cx);
+                self.entries.iter().map(|(k, e)| (k, &e.value))
+            }
+            pub fn iter_untracked(&self) -> impl Iterator<Item = (&K, &V)> {
+                self.entries.iter().map(|(k, e)| (k, &e.value))
+            }
+
+            /// Inserts a value and returns the old one.
+            ///
+            /// Notifies dependents of the entry if the key existed, and dependents of the set of keys otherwise.
+            pub fn insert(&mut self, key: K, value: V, cx: &mut $crate::StateContext) -> Option<V> {
+                self.all.notify(cx);
+                match self.entries.entry(key) {
+                    ::std::collections::$entry::Entry::Occupied(mut e) => {
+                        let e = e.get_mut();
+                        e.key.notify(cx);
+                        Some(::std::mem::replace(&mut e.value, value))
+                    }
+                    ::std::collections::$entry::Entry::Vacant(e) => {
+                        self.keys.notify(cx);
+                        e.insert(Entry {
+                            value,
+                            key: $crate::StateKey::new(cx),
+                        });
+                        None
+                    }
+                }
+            }
+            pub fn remove<Q>(&mut self, key: &Q, cx: &mut $crate::StateContext) -> Option<V>
+            where
+                K: ::std::borrow::Borrow<Q>,
+                Q: $($bound)+ + ?Sized,
+            {
+                let e = self.entries.remove(key)?;
+                e.key.notify(cx);
+                self.keys.notify(cx);
+                self.all.notify(cx);
+                Some(e.value)
+            }
+            pub fn clear(&mut self, cx: &mut $crate::StateContext) {
+                if self.entries.is_empty() {
+                    return;
+                }
+                for e in self.entries.values() {
+                    e.key.notify(cx);
+                }
+                self.entries.clear();
+                self.keys.notify(cx);
+                self.all.notify(cx);
+            }
+        }
+
+        impl<K: $($bound)+, V> $crate::state::StateInit for $name<K, V> {
+            type Init = ::std::collections::$std<K, V>;
+
+            fn init(init: ::std::collections::$std<K, V>, cx: &mut $crate::StateContext) -> Self {
+                Self::from_entries(init, cx)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<K: serde::Serialize, V: serde::Serialize> serde::Serialize for $name<K, V> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_map(self.entries.iter().map(|(k, e)| (k, &e.value)))
+            }
+        }
+        #[cfg(feature = "serde")]
+        impl<'de, K, V> $crate::DeserializeState<'de> for $name<K, V>
+        where
+            K: serde::Deserialize<'de> + $($bound)+,
+            V: serde::Deserialize<'de>,
+        {
+            fn deserialize_state<D: serde::Deserializer<'de>>(
+                deserializer: D,
+                cx: &mut $crate::StateContext,
+            ) -> Result<Self, D::Error> {
+                let entries: ::std::collections::$std<K, V> =
+                    serde::Deserialize::deserialize(deserializer)?;
+                Ok(Self::from_entries(entries, cx))
+            }
+        }
+    };
+}
+
+pub(crate) use keyed_map;
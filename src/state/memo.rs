@@ -0,0 +1,95 @@
+use crate::{DerivedTarget, StateContext, StateKey, state::StateInit};
+
+/// A cached computation over other states, for use in state type `St` of [`StateContainer<St>`](crate::StateContainer).
+///
+/// The computation is re-run only when a state it read has been notified,
+/// and dependents of the memo are notified only when the recomputed value differs from the cached one.
+///
+/// ```
+/// use sigwake::{StateContainer, state::{Memo, Value}};
+///
+/// struct St {
+///     a: Value<u32>,
+///     b: Value<u32>,
+///     sum: Memo<u32>,
+/// }
+/// let st = StateContainer::new(|cx| St {
+///     a: Value::new(1, cx),
+///     b: Value::new(2, cx),
+///     sum: Memo::new(cx),
+/// });
+/// let sum = st.update(|st, cx| *st.sum.get(cx, |cx| st.a.get(cx) + st.b.get(cx)));
+/// assert_eq!(sum, 3);
+/// ```
+#[derive(Debug)]
+pub struct Memo<T> {
+    value: Option<T>,
+    key: StateKey,
+    target: DerivedTarget,
+}
+
+impl<T> Memo<T> {
+    /// Creates a new memo that has not been computed yet.
+    pub fn new(cx: &mut StateContext) -> Self {
+        Self {
+            value: None,
+            key: StateKey::new(cx),
+            target: DerivedTarget::new(cx),
+        }
+    }
+
+    /// Returns the cached value, recomputing it with `f` if a state it depends on has changed.
+    ///
+    /// The states read by `f` become dependencies of the memo, and the memo itself becomes a dependency of the context.
+    /// If the recomputed value is not equal to the cached value, dependents of the memo are notified.
+    pub fn get(&mut self, cx: &mut StateContext, f: impl FnOnce(&mut StateContext) -> T) -> &T
+    where
+        T: PartialEq,
+    {
+        if self.target.is_dirty(cx) {
+            let value = self.target.track(&self.key, cx, f);
+            if self.value.as_ref() != Some(&value) {
+                if self.value.is_some() {
+                    self.key.notify(cx);
+                }
+                self.value = Some(value);
+            }
+        }
+        self.key.watch(cx);
+        self.target.watch(cx);
+        self.value.as_ref().unwrap()
+    }
+
+    /// Returns the cached value without recomputing it or registering a dependency.
+    ///
+    /// Returns `None` if the memo has never been computed.
+    pub fn get_untracked(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+}
+
+impl<T> StateInit for Memo<T> {
+    type Init = ();
+
+    fn init((): (), cx: &mut StateContext) -> Self {
+        Self::new(cx)
+    }
+}
+
+/// A memo is a cache, so it is serialized as a unit and recomputed after deserialization.
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Memo<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, T> crate::DeserializeState<'de> for Memo<T> {
+    fn deserialize_state<D: serde::Deserializer<'de>>(
+        deserializer: D,
+        cx: &mut StateContext,
+    ) -> Result<Self, D::Error> {
+        crate::snapshot::deserialize_unit(deserializer)?;
+        Ok(Self::new(cx))
+    }
+}
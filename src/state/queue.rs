@@ -2,7 +2,7 @@ use std::{collections::VecDeque, mem, task::Poll};
 
 use derive_ex::Ex;
 
-use crate::{StateContext, StateKey};
+use crate::{StateContext, StateKey, state::StateInit};
 
 /// A queue for use in state type `St` of [`StateContainer<St>`](crate::StateContainer).
 ///
@@ -82,3 +82,33 @@ impl<T> Iterator for QueueReaderIter<'_, T> {
         self.0.0.pop_front()
     }
 }
+
+impl<T> StateInit for Queue<T> {
+    type Init = VecDeque<T>;
+
+    fn init(init: VecDeque<T>, cx: &mut StateContext) -> Self {
+        Self {
+            items: init,
+            key: StateKey::new(cx),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Queue<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.items.serialize(serializer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> crate::DeserializeState<'de> for Queue<T> {
+    fn deserialize_state<D: serde::Deserializer<'de>>(
+        deserializer: D,
+        cx: &mut StateContext,
+    ) -> Result<Self, D::Error> {
+        Ok(Self {
+            items: serde::Deserialize::deserialize(deserializer)?,
+            key: StateKey::new(cx),
+        })
+    }
+}
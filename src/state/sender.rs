@@ -0,0 +1,220 @@
+use std::{
+    convert::Infallible,
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::Sink;
+
+use crate::{
+    StateContainer, StateContext, Target,
+    state::{EventChannel, Queue},
+};
+
+/// A channel that a sender handle can feed.
+trait Channel<T> {
+    fn poll_send(&mut self, value: &mut Option<T>, cx: &mut StateContext) -> Poll<()>;
+}
+impl<T> Channel<T> for EventChannel<T> {
+    fn poll_send(&mut self, value: &mut Option<T>, cx: &mut StateContext) -> Poll<()> {
+        EventChannel::poll_send(self, value, cx)
+    }
+}
+impl<T> Channel<T> for Queue<T> {
+    fn poll_send(&mut self, value: &mut Option<T>, cx: &mut StateContext) -> Poll<()> {
+        if let Some(value) = value.take() {
+            self.push(value, cx);
+        }
+        Poll::Ready(())
+    }
+}
+
+/// A sender with the state type of its container erased.
+trait RawSender<T>: Send {
+    fn send_blocking(&self, value: T);
+    fn poll_send(&mut self, value: &mut Option<T>, cx: &mut Context) -> Poll<()>;
+    fn clone_box(&self) -> Box<dyn RawSender<T>>;
+}
+
+struct ContainerSender<St, F> {
+    st: StateContainer<St>,
+    channel: Arc<F>,
+    target: Target<StateContainer<St>>,
+}
+impl<St, F, C, T> RawSender<T> for ContainerSender<St, F>
+where
+    St: Send + 'static,
+    F: Fn(&mut St) -> &mut C + Send + Sync + 'static,
+    C: Channel<T>,
+{
+    fn send_blocking(&self, value: T) {
+        let mut value = Some(value);
+        self.st
+            .wait_blocking(|st, cx| (self.channel)(st).poll_send(&mut value, cx));
+    }
+    fn poll_send(&mut self, value: &mut Option<T>, cx: &mut Context) -> Poll<()> {
+        if value.is_none() {
+            return Poll::Ready(());
+        }
+        let channel = &self.channel;
+        self.target
+            .poll_fn(|st, scx| channel(st).poll_send(value, scx), cx)
+    }
+    fn clone_box(&self) -> Box<dyn RawSender<T>> {
+        Box::new(ContainerSender {
+            st: self.st.clone(),
+            channel: self.channel.clone(),
+            target: Target::new(&self.st),
+        })
+    }
+}
+
+/// The state shared by [`EventSender`] and [`QueueSender`]: the erased sender and the value accepted by the `Sink` but not sent yet.
+struct SenderCore<T> {
+    raw: Box<dyn RawSender<T>>,
+    pending: Option<T>,
+}
+impl<T> SenderCore<T> {
+    fn new<St, C>(
+        st: &StateContainer<St>,
+        channel: impl Fn(&mut St) -> &mut C + Send + Sync + 'static,
+    ) -> Self
+    where
+        St: Send + 'static,
+        C: Channel<T> + 'static,
+    {
+        Self {
+            raw: Box::new(ContainerSender {
+                st: st.clone(),
+                channel: Arc::new(channel),
+                target: Target::new(st),
+            }),
+            pending: None,
+        }
+    }
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<(), Infallible>> {
+        self.raw.poll_send(&mut self.pending, cx).map(Ok)
+    }
+    fn start_send(&mut self, item: T) -> Result<(), Infallible> {
+        assert!(
+            self.pending.is_none(),
+            "`poll_ready` must be called before `start_send`"
+        );
+        self.pending = Some(item);
+        Ok(())
+    }
+}
+// The pending value is never pinned.
+impl<T> Unpin for SenderCore<T> {}
+impl<T> Clone for SenderCore<T> {
+    fn clone(&self) -> Self {
+        Self {
+            raw: self.raw.clone_box(),
+            pending: None,
+        }
+    }
+}
+
+/// A handle that sends events to an [`EventChannel`] in a [`StateContainer`], created by [`StateContainer::event_sender`].
+///
+/// The handle can be cloned and moved to other threads, and implements [`Sink`] so that streams can be
+/// [forwarded](futures::StreamExt::forward) to the channel.
+/// As a `Sink`, it waits while a channel with [`OverflowPolicy::Block`](crate::state::OverflowPolicy::Block) is full.
+/// A clone does not carry the event that the original has accepted but not sent yet.
+///
+/// The inherent [`send`](Self::send) shadows [`SinkExt::send`](futures::SinkExt::send),
+/// which can be called as `SinkExt::send(&mut sender, value)`.
+pub struct EventSender<T>(SenderCore<T>);
+
+impl<T> EventSender<T> {
+    /// Sends an event, blocking the current thread while a channel with
+    /// [`OverflowPolicy::Block`](crate::state::OverflowPolicy::Block) is full.
+    pub fn send(&self, value: T) {
+        self.0.raw.send_blocking(value);
+    }
+}
+impl<T> Clone for EventSender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+impl<T> fmt::Debug for EventSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventSender").finish_non_exhaustive()
+    }
+}
+impl<T> Sink<T> for EventSender<T> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Infallible> {
+        self.get_mut().0.start_send(item)
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+}
+
+/// A handle that pushes items to a [`Queue`] in a [`StateContainer`], created by [`StateContainer::queue_sender`].
+///
+/// The handle can be cloned and moved to other threads, and implements [`Sink`] so that streams can be
+/// [forwarded](futures::StreamExt::forward) to the queue.
+pub struct QueueSender<T>(SenderCore<T>);
+
+impl<T> QueueSender<T> {
+    /// Pushes an item to the queue.
+    pub fn send(&self, value: T) {
+        self.0.raw.send_blocking(value);
+    }
+}
+impl<T> Clone for QueueSender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+impl<T> fmt::Debug for QueueSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueueSender").finish_non_exhaustive()
+    }
+}
+impl<T> Sink<T> for QueueSender<T> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Infallible> {
+        self.get_mut().0.start_send(item)
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+}
+
+impl<St: Send + 'static> StateContainer<St> {
+    /// Creates a handle that sends events to the channel returned by `channel`.
+    pub fn event_sender<T: 'static>(
+        &self,
+        channel: impl Fn(&mut St) -> &mut EventChannel<T> + Send + Sync + 'static,
+    ) -> EventSender<T> {
+        EventSender(SenderCore::new(self, channel))
+    }
+
+    /// Creates a handle that pushes items to the queue returned by `queue`.
+    pub fn queue_sender<T: 'static>(
+        &self,
+        queue: impl Fn(&mut St) -> &mut Queue<T> + Send + Sync + 'static,
+    ) -> QueueSender<T> {
+        QueueSender(SenderCore::new(self, queue))
+    }
+}
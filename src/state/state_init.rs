@@ -0,0 +1,11 @@
+use crate::StateContext;
+
+/// A state type that can be created from a plain initial value.
+///
+/// This is what `#[derive(State)]` uses to build each field of a state struct.
+pub trait StateInit: Sized {
+    /// The plain value that the state is created from.
+    type Init;
+
+    fn init(init: Self::Init, cx: &mut StateContext) -> Self;
+}
@@ -1,4 +1,6 @@
-use crate::{StateContext, StateKey};
+use std::ops::{Deref, DerefMut};
+
+use crate::{StateContext, StateKey, state::StateInit};
 
 pub struct Value<T> {
     value: T,
@@ -17,13 +19,143 @@ impl<T> Value<T> {
     pub fn get_untracked(&self) -> &T {
         &self.value
     }
-    pub fn get_mut(&mut self, cx: &mut StateContext) -> &mut T {
-        self.key.notify(cx);
+
+    /// Returns a guard that gives mutable access to the value.
+    ///
+    /// Dependents are notified when the guard is dropped, but only if the value was accessed mutably through it,
+    /// even if the access did not change the value.
+    /// Use [`get_mut_if_changed`](Self::get_mut_if_changed) to notify only when the value has changed.
+    pub fn get_mut<'a>(&'a mut self, cx: &'a mut StateContext) -> ValueMut<'a, T> {
         self.key.watch(cx);
-        &mut self.value
+        ValueMut {
+            value: self,
+            cx,
+            is_modified: false,
+            old: None,
+        }
+    }
+
+    /// Like [`get_mut`](Self::get_mut), but notifies dependents only if the value differs from the one
+    /// before the guard was created.
+    ///
+    /// The value is cloned when the guard is created, to compare with it when the guard is dropped.
+    pub fn get_mut_if_changed<'a>(&'a mut self, cx: &'a mut StateContext) -> ValueMut<'a, T>
+    where
+        T: PartialEq + Clone,
+    {
+        self.key.watch(cx);
+        let old = self.value.clone();
+        ValueMut {
+            value: self,
+            cx,
+            is_modified: false,
+            old: Some((old, T::eq)),
+        }
     }
     pub fn set(&mut self, value: T, cx: &mut StateContext) {
         self.key.notify(cx);
         self.value = value;
     }
+
+    /// Sets the value and returns the old one, notifying dependents unconditionally.
+    pub fn replace(&mut self, value: T, cx: &mut StateContext) -> T {
+        self.key.notify(cx);
+        std::mem::replace(&mut self.value, value)
+    }
+
+    /// Sets the value, notifying dependents only if it is not equal to the current value.
+    ///
+    /// Returns `true` if the value has changed.
+    pub fn set_if_changed(&mut self, value: T, cx: &mut StateContext) -> bool
+    where
+        T: PartialEq,
+    {
+        self.set_if_changed_by(value, T::eq, cx)
+    }
+
+    /// Sets the value, notifying dependents only if `eq` reports that it differs from the current value.
+    ///
+    /// `eq` is called with the current value and the new value.
+    /// Returns `true` if the value has changed.
+    pub fn set_if_changed_by(
+        &mut self,
+        value: T,
+        eq: impl FnOnce(&T, &T) -> bool,
+        cx: &mut StateContext,
+    ) -> bool {
+        if eq(&self.value, &value) {
+            return false;
+        }
+        self.set(value, cx);
+        true
+    }
+
+    /// Replaces the value with the result of `f`, notifying dependents only if the result differs.
+    ///
+    /// Returns `true` if the value has changed.
+    pub fn update_with(&mut self, f: impl FnOnce(&T) -> T, cx: &mut StateContext) -> bool
+    where
+        T: PartialEq,
+    {
+        let value = f(&self.value);
+        self.set_if_changed(value, cx)
+    }
+}
+
+type EqFn<T> = fn(&T, &T) -> bool;
+
+/// Mutable access to the value of a [`Value`], returned by [`Value::get_mut`] and [`Value::get_mut_if_changed`].
+pub struct ValueMut<'a, T> {
+    value: &'a mut Value<T>,
+    cx: &'a mut StateContext,
+    is_modified: bool,
+    /// The value before the guard was created and the function to compare it, for `get_mut_if_changed`.
+    old: Option<(T, EqFn<T>)>,
+}
+impl<T> Deref for ValueMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value.value
+    }
+}
+impl<T> DerefMut for ValueMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.is_modified = true;
+        &mut self.value.value
+    }
+}
+impl<T> Drop for ValueMut<'_, T> {
+    fn drop(&mut self) {
+        let is_unchanged = self
+            .old
+            .as_ref()
+            .is_some_and(|(old, eq)| eq(old, &self.value.value));
+        if self.is_modified && !is_unchanged {
+            self.value.key.notify(self.cx);
+        }
+    }
+}
+
+impl<T> StateInit for Value<T> {
+    type Init = T;
+
+    fn init(init: T, cx: &mut StateContext) -> Self {
+        Self::new(init, cx)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Value<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> crate::DeserializeState<'de> for Value<T> {
+    fn deserialize_state<D: serde::Deserializer<'de>>(
+        deserializer: D,
+        cx: &mut StateContext,
+    ) -> Result<Self, D::Error> {
+        Ok(Self::new(T::deserialize(deserializer)?, cx))
+    }
 }
@@ -0,0 +1,186 @@
+use std::mem;
+
+use crate::{
+    StateContext, StateKey,
+    state::{ElementMut, StateInit},
+};
+
+/// A vector for use in state type `St` of [`StateContainer<St>`](crate::StateContainer) that tracks dependencies per index.
+///
+/// Each index has its own [`StateKey`], so reading one element only depends on that index.
+/// Reading the length depends on the length, and iterating depends on the whole vector.
+/// Reading an index out of bounds depends on the length, because the index may become valid later.
+#[derive(Debug)]
+pub struct Vec<T> {
+    values: std::vec::Vec<T>,
+    keys: std::vec::Vec<StateKey>,
+    len: StateKey,
+    all: StateKey,
+}
+
+impl<T> Vec<T> {
+    /// Creates a new empty vector.
+    pub fn new(cx: &mut StateContext) -> Self {
+        Self {
+            values: std::vec::Vec::new(),
+            keys: std::vec::Vec::new(),
+            len: StateKey::new(cx),
+            all: StateKey::new(cx),
+        }
+    }
+
+    pub fn get(&self, index: usize, cx: &mut StateContext) -> Option<&T> {
+        match self.values.get(index) {
+            Some(value) => {
+                self.keys[index].watch(cx);
+                Some(value)
+            }
+            None => {
+                self.len.watch(cx);
+                None
+            }
+        }
+    }
+    pub fn get_untracked(&self, index: usize) -> Option<&T> {
+        self.values.get(index)
+    }
+
+    /// Returns a guard that gives mutable access to the element.
+    ///
+    /// Dependents of the index and of the whole vector are notified when the guard is dropped,
+    /// but only if the element was accessed mutably through it.
+    pub fn get_mut<'a>(
+        &'a mut self,
+        index: usize,
+        cx: &'a mut StateContext,
+    ) -> Option<ElementMut<'a, T>> {
+        match self.values.get_mut(index) {
+            Some(value) => Some(ElementMut::new(value, &self.keys[index], &self.all, cx)),
+            None => {
+                self.len.watch(cx);
+                None
+            }
+        }
+    }
+
+    /// Replaces the element at `index` and returns the old one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: T, cx: &mut StateContext) -> T {
+        let old = mem::replace(&mut self.values[index], value);
+        self.keys[index].notify(cx);
+        self.all.notify(cx);
+        old
+    }
+    pub fn len(&self, cx: &mut StateContext) -> usize {
+        self.len.watch(cx);
+        self.values.len()
+    }
+    pub fn is_empty(&self, cx: &mut StateContext) -> bool {
+        self.len(cx) == 0
+    }
+    pub fn iter(&self, cx: &mut StateContext) -> std::slice::Iter<'_, T> {
+        self.all.watch(cx);
+        self.values.iter()
+    }
+    pub fn as_slice_untracked(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn push(&mut self, value: T, cx: &mut StateContext) {
+        self.values.push(value);
+        self.keys.push(StateKey::new(cx));
+        self.len.notify(cx);
+        self.all.notify(cx);
+    }
+    pub fn pop(&mut self, cx: &mut StateContext) -> Option<T> {
+        let value = self.values.pop()?;
+        self.keys.pop().unwrap().notify(cx);
+        self.len.notify(cx);
+        self.all.notify(cx);
+        Some(value)
+    }
+
+    /// Inserts an element at `index`, shifting all elements after it to the right.
+    ///
+    /// Notifies dependents of every index from `index` to the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: T, cx: &mut StateContext) {
+        self.values.insert(index, value);
+        self.keys.push(StateKey::new(cx));
+        self.notify_from(index, cx);
+        self.len.notify(cx);
+    }
+
+    /// Removes and returns the element at `index`, shifting all elements after it to the left.
+    ///
+    /// Notifies dependents of every index from `index` to the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize, cx: &mut StateContext) -> T {
+        let value = self.values.remove(index);
+        self.notify_from(index, cx);
+        self.keys.pop();
+        self.len.notify(cx);
+        value
+    }
+    pub fn clear(&mut self, cx: &mut StateContext) {
+        if self.values.is_empty() {
+            return;
+        }
+        self.notify_from(0, cx);
+        self.values.clear();
+        self.keys.clear();
+        self.len.notify(cx);
+    }
+    fn notify_from(&self, index: usize, cx: &mut StateContext) {
+        for key in &self.keys[index..] {
+            key.notify(cx);
+        }
+        self.all.notify(cx);
+    }
+}
+
+impl<T> StateInit for Vec<T> {
+    type Init = std::vec::Vec<T>;
+
+    fn init(init: std::vec::Vec<T>, cx: &mut StateContext) -> Self {
+        let keys = init.iter().map(|_| StateKey::new(cx)).collect();
+        Self {
+            values: init,
+            keys,
+            len: StateKey::new(cx),
+            all: StateKey::new(cx),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Vec<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.values.serialize(serializer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> crate::DeserializeState<'de> for Vec<T> {
+    fn deserialize_state<D: serde::Deserializer<'de>>(
+        deserializer: D,
+        cx: &mut StateContext,
+    ) -> Result<Self, D::Error> {
+        let values: std::vec::Vec<T> = serde::Deserialize::deserialize(deserializer)?;
+        let keys = values.iter().map(|_| StateKey::new(cx)).collect();
+        Ok(Self {
+            values,
+            keys,
+            len: StateKey::new(cx),
+            all: StateKey::new(cx),
+        })
+    }
+}
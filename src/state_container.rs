@@ -1,11 +1,17 @@
-use std::mem::transmute;
-use std::sync::{Arc, MutexGuard};
-use std::task::{Context, Poll, Waker};
-use std::time::Instant;
+use std::borrow::Cow;
+use std::fmt;
+use std::mem::{self, transmute};
+use std::sync::{Arc, LockResult, PoisonError, TryLockError};
+use std::task::{Context, Poll, Waker, ready};
+use std::time::{Duration, Instant};
 use std::{future::poll_fn, sync::Mutex};
 
-use crate::time::{SpawnAtTask, spawn_at};
+use crate::dependency_graph::{DependencyGraph, SourceNode, TargetNode};
+use crate::stats::ContainerStats;
+use crate::time::{AnyTime, MissedTickBehavior, SpawnAtTask, TimerBackend, spawn_at_with};
 use crate::utils::Action;
+use crate::utils::async_mutex::{AsyncMutex, AsyncMutexGuard};
+use crate::utils::trace::{trace_event, trace_span};
 use ::futures::{Stream, stream};
 use derive_ex::Ex;
 
@@ -13,66 +19,333 @@ use crate::utils::bipartite_graph::*;
 use crate::utils::inf_vec::*;
 use crate::utils::usize_set::USizeSet;
 
-#[derive(Debug)]
-struct StateGraph {
-    g: BipartiteGraph,
-    wakers: InfVec<Option<Action>>,
+#[derive(Ex)]
+#[derive_ex(Debug)]
+pub(crate) struct StateGraph {
+    g: BipartiteGraph<Option<Cow<'static, str>>>,
+    targets: InfVec<TargetData>,
     wake_at: Option<Instant>,
     source_set: USizeSet,
     source_remove: Arc<Mutex<Vec<XKey>>>,
+    target_remove: Arc<Mutex<Vec<YKey>>>,
+    every: Option<(Duration, MissedTickBehavior)>,
+    derived_wakes: Vec<XKey>,
+    wakes: Vec<Action>,
+    is_deferred: bool,
+    is_read_only: bool,
+    #[debug(ignore)]
+    timer: Option<Arc<dyn TimerBackend>>,
+    stats: ContainerStats,
 }
 impl StateGraph {
     pub fn new() -> Self {
         Self {
             g: BipartiteGraph::new(),
-            wakers: InfVec::new(),
+            targets: InfVec::new(),
             wake_at: None,
             source_set: USizeSet::new(),
             source_remove: Arc::new(Mutex::new(Vec::new())),
+            target_remove: Arc::new(Mutex::new(Vec::new())),
+            every: None,
+            derived_wakes: Vec::new(),
+            wakes: Vec::new(),
+            is_deferred: false,
+            is_read_only: false,
+            timer: None,
+            stats: ContainerStats::default(),
+        }
+    }
+
+    /// Creates a graph that only records the dependencies of a target, for reads under a shared lock.
+    pub fn new_read_only() -> Self {
+        Self {
+            is_read_only: true,
+            ..Self::new()
         }
     }
+    fn assert_writable(&self) {
+        assert!(
+            !self.is_read_only,
+            "states cannot be created or notified while only reading"
+        );
+    }
 
+    pub fn set_timer_backend(&mut self, backend: impl TimerBackend + 'static) {
+        self.timer = Some(Arc::new(backend));
+    }
+    pub fn timer_backend(&self) -> Option<Arc<dyn TimerBackend>> {
+        self.timer.clone()
+    }
+    pub fn clear_sources(&mut self) {
+        self.source_set.clear();
+    }
     fn set_source(&mut self, x: XKey) {
         self.source_set.insert(x.0);
     }
-    fn remove_target(&mut self, y: YKey) {
+    pub fn remove_target(&mut self, y: YKey) {
         self.g.remove_y(y);
-        self.wakers[y.0] = None;
+        self.targets[y.0] = TargetData::default();
+    }
+    fn disarm_target(&mut self, y: YKey) {
+        self.targets[y.0].waker = None;
+    }
+    fn target_state(&self, y: YKey) -> TargetState {
+        self.targets[y.0].state
     }
     fn apply_source_remove(&mut self) {
         let source_removing = self.source_remove.clone();
-        let mut xs = source_removing.lock().unwrap();
+        let mut xs = source_removing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
         for x in xs.drain(..) {
             self.g.remove_x(x);
         }
+        let target_removing = self.target_remove.clone();
+        let mut ys = target_removing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for y in ys.drain(..) {
+            self.remove_target(y);
+        }
+    }
+
+    pub fn dependency_graph(&mut self) -> DependencyGraph {
+        self.apply_source_remove();
+        let sources = self
+            .g
+            .xs()
+            .map(|(x, name)| SourceNode {
+                id: x.0,
+                name: name.clone(),
+            })
+            .collect();
+        let targets = self
+            .g
+            .ys()
+            .map(|(y, _)| {
+                let t = &self.targets[y.0];
+                let mut sources: Vec<_> = self.g.xs_from_y(y).map(|(x, _)| x.0).collect();
+                sources.sort_unstable();
+                TargetNode {
+                    id: y.0,
+                    sources,
+                    derived: t.derived.map(|x| x.0),
+                    is_woken: t.state != TargetState::Clean,
+                }
+            })
+            .collect();
+        DependencyGraph { sources, targets }
+    }
+
+    pub fn stats(&mut self) -> ContainerStats {
+        self.apply_source_remove();
+        ContainerStats {
+            keys: self.g.x_len(),
+            targets: self.g.y_len(),
+            edges: self.g.edge_len(),
+            ..self.stats
+        }
+    }
+    pub fn record_lock_wait(&mut self, wait: Duration) {
+        self.stats.lock_contentions += 1;
+        self.stats.lock_wait += wait;
     }
 
     fn wake(&mut self, x: XKey) {
+        self.assert_writable();
+        self.stats.notifications += 1;
+        let wakes_len = self.wakes.len();
         for (y, _) in self.g.ys_from_x(x) {
-            wake(&mut self.wakers, y);
+            wake(
+                &mut self.targets,
+                y,
+                true,
+                &mut self.derived_wakes,
+                &mut self.wakes,
+            );
+        }
+        while let Some(x) = self.derived_wakes.pop() {
+            for (y, _) in self.g.ys_from_x(x) {
+                wake(
+                    &mut self.targets,
+                    y,
+                    false,
+                    &mut self.derived_wakes,
+                    &mut self.wakes,
+                );
+            }
+        }
+        self.stats.wakeups += (self.wakes.len() - wakes_len) as u64;
+        if !self.is_deferred {
+            for waker in self.wakes.drain(..) {
+                waker.call();
+            }
+        }
+    }
+    #[cfg(feature = "serde")]
+    fn wake_all(&mut self) {
+        let xs: Vec<XKey> = self.g.xs().map(|(x, _)| x).collect();
+        for x in xs {
+            self.wake(x);
         }
     }
     pub fn context(&mut self) -> &mut StateContext {
         self.apply_source_remove();
         self.wake_at = None;
+        self.every = None;
+        self.is_deferred = false;
         StateContext::new(self)
     }
+    /// Discards the work left in progress by a panic, so that the graph can be used again.
+    ///
+    /// Wakers collected before the panic are kept and called by the next [`take_deferred_wakes`](Self::take_deferred_wakes).
+    pub fn recover(&mut self) {
+        self.source_set.clear();
+        self.derived_wakes.clear();
+    }
+    pub fn take_deferred_wakes(&mut self) -> DeferredWakes {
+        self.is_deferred = false;
+        DeferredWakes(mem::take(&mut self.wakes))
+    }
     fn commit_target<A: Into<Action>>(
         &mut self,
         waker: impl Fn() -> A,
     ) -> (Option<YKey>, Option<SpawnAtTask>) {
+        let y = self.insert_target();
+        self.targets[y.0].waker = Some(waker().into());
+        let task = self.wake_at.map(|at| {
+            trace_event!(target_id = y.0, ?at, "notify_at registered");
+            #[cfg(feature = "tracing")]
+            let action = {
+                let action: Action = waker().into();
+                Action::new(move || {
+                    trace_event!(target_id = y.0, "notify_at fired");
+                    action.call();
+                })
+            };
+            #[cfg(not(feature = "tracing"))]
+            let action = waker().into();
+            spawn_at_with(self.timer.as_ref(), action, at.into())
+        });
+        (Some(y), task)
+    }
+    /// Advances the schedule requested by [`StateContext::notify_every`] and arms its next tick.
+    fn commit_every(&mut self, ticker: &mut Option<Ticker>) {
+        let Some((period, missed_tick_behavior)) = self.every.take() else {
+            *ticker = None;
+            return;
+        };
+        let now = crate::time::now();
+        let t = match ticker {
+            Some(t) if t.period == period && t.missed_tick_behavior == missed_tick_behavior => t,
+            _ => ticker.insert(Ticker {
+                period,
+                missed_tick_behavior,
+                next: now + period,
+            }),
+        };
+        if t.next <= now {
+            t.next = missed_tick_behavior.next_tick(t.next, period, now);
+        }
+        StateContext::new(self).notify_at(t.next);
+    }
+    /// Commits a target whose dependencies were recorded in the read-only graph `recorded`.
+    pub fn commit_recorded<A: Into<Action>>(
+        &mut self,
+        recorded: &mut StateGraph,
+        ticker: &mut Option<Ticker>,
+        waker: impl Fn() -> A,
+    ) -> (Option<YKey>, Option<SpawnAtTask>) {
+        self.apply_source_remove();
+        mem::swap(&mut self.source_set, &mut recorded.source_set);
+        self.wake_at = recorded.wake_at;
+        self.every = recorded.every;
+        self.commit_every(ticker);
+        let ret = self.commit_target(waker);
+        mem::swap(&mut self.source_set, &mut recorded.source_set);
+        ret
+    }
+    fn commit_derived(&mut self, x: XKey) -> YKey {
+        let y = self.insert_target();
+        self.targets[y.0].derived = Some(x);
+        y
+    }
+    fn insert_target(&mut self) -> YKey {
         let y = self.g.insert_y(());
         for x in self.source_set.iter() {
             self.g.insert_edge(XKey(x), y, ());
         }
-        self.wakers[y.0] = Some(waker().into());
-        let task = self.wake_at.map(|at| spawn_at(waker(), at));
-        (Some(y), task)
+        y
     }
 }
-fn wake(wakers: &mut InfVec<Option<Action>>, y: YKey) {
-    if let Some(waker) = wakers[y.0].take() {
-        waker.call();
+
+/// The schedule of a periodic notification, kept by a target across its re-runs.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Ticker {
+    period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+    next: Instant,
+}
+
+#[derive(Debug, Default)]
+struct TargetData {
+    waker: Option<Action>,
+    derived: Option<XKey>,
+    state: TargetState,
+}
+
+/// Why a target has been woken since it was committed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum TargetState {
+    #[default]
+    Clean,
+    /// Only derived states that the target depends on may have changed.
+    MaybeDirty,
+    /// A state that the target depends on has changed.
+    Dirty,
+}
+
+fn wake(
+    targets: &mut InfVec<TargetData>,
+    y: YKey,
+    is_dirty: bool,
+    derived_wakes: &mut Vec<XKey>,
+    wakes: &mut Vec<Action>,
+) {
+    let t = &mut targets[y.0];
+    let is_clean = t.state == TargetState::Clean;
+    trace_event!(
+        target_id = y.0,
+        is_dirty,
+        was_clean = is_clean,
+        "target woken"
+    );
+    if is_dirty {
+        t.state = TargetState::Dirty;
+    } else if is_clean {
+        t.state = TargetState::MaybeDirty;
+    }
+    if is_clean {
+        if let Some(x) = t.derived {
+            derived_wakes.push(x);
+        }
+    }
+    if let Some(waker) = t.waker.take() {
+        wakes.push(waker);
+    }
+}
+
+/// Wakers collected while notifications were deferred, called when dropped.
+///
+/// Must be dropped after the container lock is released.
+#[must_use]
+pub(crate) struct DeferredWakes(Vec<Action>);
+
+impl Drop for DeferredWakes {
+    fn drop(&mut self) {
+        for waker in self.0.drain(..) {
+            waker.call();
+        }
     }
 }
 
@@ -85,7 +358,16 @@ pub struct StateKey {
 }
 impl StateKey {
     pub fn new(cx: &mut StateContext) -> Self {
-        let x = cx.0.g.insert_x(());
+        Self::new_raw(None, cx)
+    }
+
+    /// Creates a key with a name shown in [`DependencyGraph`].
+    pub fn new_named(name: impl Into<Cow<'static, str>>, cx: &mut StateContext) -> Self {
+        Self::new_raw(Some(name.into()), cx)
+    }
+    fn new_raw(name: Option<Cow<'static, str>>, cx: &mut StateContext) -> Self {
+        cx.0.assert_writable();
+        let x = cx.0.g.insert_x(name);
         Self {
             x,
             source_remove: cx.0.source_remove.clone(),
@@ -95,12 +377,20 @@ impl StateKey {
         cx.0.set_source(self.x);
     }
     pub fn notify(&self, cx: &mut StateContext) {
+        trace_event!(
+            key = self.x.0,
+            name = cx.0.g.get_x(self.x).and_then(|name| name.as_deref()),
+            "notify"
+        );
         cx.0.wake(self.x);
     }
 }
 impl Drop for StateKey {
     fn drop(&mut self) {
-        self.source_remove.lock().unwrap().push(self.x);
+        self.source_remove
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(self.x);
     }
 }
 
@@ -119,102 +409,586 @@ impl StateContext {
         }
         self.0.wake_at = Some(at);
     }
+
+    /// Notifies the current target once `duration` has elapsed from now.
+    ///
+    /// Like [`notify_at`](Self::notify_at), this only applies to the current run of the target,
+    /// so calling it on every run re-arms the timer each time the target is re-run.
+    pub fn notify_after(&mut self, duration: Duration) {
+        self.notify_at(crate::time::now() + duration);
+    }
+
+    /// Notifies the current target every `period` for as long as each run of the target calls this method.
+    ///
+    /// Unlike calling [`notify_after`](Self::notify_after) on every run, the ticks follow a fixed schedule
+    /// that is kept across runs, and re-runs caused by other states do not shift it.
+    /// `missed_tick_behavior` decides what happens when ticks are missed.
+    /// If this method is called more than once in a run, the shortest period is used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn notify_every(&mut self, period: Duration, missed_tick_behavior: MissedTickBehavior) {
+        assert!(!period.is_zero(), "`period` must be non-zero");
+        if let Some((p, _)) = self.0.every {
+            if p <= period {
+                return;
+            }
+        }
+        self.0.every = Some((period, missed_tick_behavior));
+    }
+
+    /// Notifies every state in the container.
+    #[cfg(feature = "serde")]
+    pub(crate) fn notify_all(&mut self) {
+        self.0.wake_all();
+    }
+
+    /// Defers waking dependents until the container lock is released.
+    ///
+    /// Until the current closure returns, notifications only mark dependents as woken,
+    /// and each woken dependent is woken once after the lock is released,
+    /// no matter how many of its sources have been notified.
+    pub fn defer_notifications(&mut self) {
+        self.0.is_deferred = true;
+    }
+}
+
+/// A node that is both a target of the states it reads and a source for its own dependents.
+///
+/// When a state it depends on changes, the node becomes dirty and its dependents are only
+/// notified that it *may* have changed. It is up to the owner to recompute the value and
+/// notify its own [`StateKey`] if the result actually differs.
+#[derive(Ex)]
+#[derive_ex(Debug)]
+pub(crate) struct DerivedTarget {
+    y: Option<YKey>,
+    wake_at: Option<Instant>,
+    ticker: Option<Ticker>,
+    #[debug(ignore)]
+    target_remove: Arc<Mutex<Vec<YKey>>>,
+}
+impl DerivedTarget {
+    pub fn new(cx: &mut StateContext) -> Self {
+        Self {
+            y: None,
+            wake_at: None,
+            ticker: None,
+            target_remove: cx.0.target_remove.clone(),
+        }
+    }
+    pub fn is_dirty(&self, cx: &StateContext) -> bool {
+        let Some(y) = self.y else {
+            return true;
+        };
+        if cx.0.target_state(y) != TargetState::Clean {
+            return true;
+        }
+        if let Some(wake_at) = self.wake_at {
+            if crate::time::now() >= wake_at {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Runs `f` while recording the states it reads as the dependencies of this node.
+    pub fn track<T>(
+        &mut self,
+        key: &StateKey,
+        cx: &mut StateContext,
+        f: impl FnOnce(&mut StateContext) -> T,
+    ) -> T {
+        if let Some(y) = self.y.take() {
+            cx.0.remove_target(y);
+        }
+        let _span = trace_span!(
+            "recompute",
+            key = key.x.0,
+            name = cx.0.g.get_x(key.x).and_then(|name| name.as_deref())
+        );
+        let source_set = mem::take(&mut cx.0.source_set);
+        let wake_at = cx.0.wake_at.take();
+        let every = cx.0.every.take();
+        let value = f(cx);
+        cx.0.commit_every(&mut self.ticker);
+        self.y = Some(cx.0.commit_derived(key.x));
+        self.wake_at = cx.0.wake_at;
+        cx.0.source_set = source_set;
+        cx.0.wake_at = wake_at;
+        cx.0.every = every;
+        value
+    }
+
+    /// Propagates the time at which this node becomes dirty to the current target.
+    pub fn watch(&self, cx: &mut StateContext) {
+        if let Some(wake_at) = self.wake_at {
+            cx.notify_at(wake_at);
+        }
+    }
+}
+impl Drop for DerivedTarget {
+    fn drop(&mut self) {
+        if let Some(y) = self.y.take() {
+            self.target_remove
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(y);
+        }
+    }
+}
+
+pub(crate) struct RawStateContainer<St> {
+    pub g: StateGraph,
+    pub st: St,
+    pub poison_policy: PoisonPolicy,
+}
+impl<St> RawStateContainer<St> {
+    pub fn new(f: impl FnOnce(&mut StateContext) -> St) -> Self {
+        let mut g = StateGraph::new();
+        let st = f(g.context());
+        Self {
+            g,
+            st,
+            poison_policy: PoisonPolicy::default(),
+        }
+    }
+}
+
+/// Shared access to the dependency graph and the state, implemented by [`StateContainer`] and
+/// [`LocalStateContainer`](crate::LocalStateContainer) so that both share the tracking logic.
+pub(crate) trait ContainerRef: Clone {
+    type St;
+    fn try_with<T>(
+        &self,
+        f: impl FnOnce(&mut StateGraph, &mut Self::St) -> T,
+    ) -> Result<T, ContainerPoisoned>;
+
+    /// Like [`try_with`](Self::try_with), but returns `Poll::Pending` and wakes `cx` later
+    /// instead of blocking the thread while the container is locked.
+    ///
+    /// `wait_start` keeps the time of the first `Poll::Pending` across polls,
+    /// so that the whole wait is recorded when the lock is acquired.
+    fn try_poll_with<T>(
+        &self,
+        _cx: &Context,
+        _wait_start: &mut Option<Instant>,
+        f: impl FnOnce(&mut StateGraph, &mut Self::St) -> T,
+    ) -> Poll<Result<T, ContainerPoisoned>> {
+        Poll::Ready(self.try_with(f))
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut StateGraph, &mut Self::St) -> T) -> T {
+        self.try_with(f).unwrap_or_else(|e| panic!("{e}"))
+    }
+    fn poll_with<T>(
+        &self,
+        cx: &Context,
+        wait_start: &mut Option<Instant>,
+        f: impl FnOnce(&mut StateGraph, &mut Self::St) -> T,
+    ) -> Poll<T> {
+        self.try_poll_with(cx, wait_start, f)
+            .map(|r| r.unwrap_or_else(|e| panic!("{e}")))
+    }
+}
+impl<St> ContainerRef for StateContainer<St> {
+    type St = St;
+    fn try_with<T>(
+        &self,
+        f: impl FnOnce(&mut StateGraph, &mut St) -> T,
+    ) -> Result<T, ContainerPoisoned> {
+        let ss = &mut *self.check_poison(self.lock())?;
+        Ok(f(&mut ss.g, &mut ss.st))
+    }
+    fn try_poll_with<T>(
+        &self,
+        cx: &Context,
+        wait_start: &mut Option<Instant>,
+        f: impl FnOnce(&mut StateGraph, &mut St) -> T,
+    ) -> Poll<Result<T, ContainerPoisoned>> {
+        let ss = ready!(self.poll_lock(cx, wait_start));
+        Poll::Ready(self.check_poison(ss).map(|mut ss| {
+            let ss = &mut *ss;
+            f(&mut ss.g, &mut ss.st)
+        }))
+    }
 }
 
-struct RawStateContainer<St> {
-    g: StateGraph,
-    st: St,
+/// How a [`StateContainer`] behaves after a panic inside one of its closures has poisoned it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PoisonPolicy {
+    /// Panic in every later access, and return [`ContainerPoisoned`] from the `try_` variants.
+    #[default]
+    Panic,
+    /// Clear the poison and continue with the state as the panicking closure left it.
+    Recover,
 }
 
 #[derive(Ex)]
 #[derive_ex(Clone, bound())]
-pub struct StateContainer<St>(Arc<Mutex<RawStateContainer<St>>>);
+pub struct StateContainer<St>(Arc<AsyncMutex<RawStateContainer<St>>>);
 
 impl<St> StateContainer<St> {
     pub fn new(f: impl FnOnce(&mut StateContext) -> St) -> Self {
-        let mut g = StateGraph::new();
-        let st = f(g.context());
-        Self(Arc::new(Mutex::new(RawStateContainer { g, st })))
+        Self(Arc::new(AsyncMutex::new(RawStateContainer::new(f))))
     }
 
-    pub async fn poll_fn<U>(&self, mut f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>) -> U {
+    pub async fn poll_fn<U>(&self, f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>) -> U {
+        poll_fn_raw(self, f).await
+    }
+
+    /// Like [`poll_fn`](Self::poll_fn), but returns [`ContainerPoisoned`] instead of panicking
+    /// if the container has been poisoned.
+    pub async fn try_poll_fn<U>(
+        &self,
+        mut f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>,
+    ) -> Result<U, ContainerPoisoned> {
         let mut t = Target::new(self);
-        poll_fn(|cx| t.poll_fn(&mut f, cx)).await
+        poll_fn(|cx| t.try_poll_fn(&mut f, cx)).await
+    }
+
+    /// Like [`poll_fn`](Self::poll_fn), but gives up with [`Timeout`] when `deadline` is reached.
+    pub async fn poll_fn_until<U>(
+        &self,
+        deadline: impl Into<AnyTime>,
+        f: impl FnMut(&mut St, &mut StateContext) -> Poll<U>,
+    ) -> Result<U, Timeout> {
+        poll_fn_until_raw(self, deadline.into(), f).await
     }
     pub fn poll_fn_stream<U>(
         &self,
-        mut f: impl FnMut(&mut St, &mut StateContext) -> Poll<Option<U>> + 'static,
+        f: impl FnMut(&mut St, &mut StateContext) -> Poll<Option<U>> + 'static,
     ) -> impl Stream<Item = U> + 'static
     where
         St: 'static,
     {
-        let mut t = Target::new(self);
-        stream::poll_fn(move |cx| t.poll_fn(&mut f, cx))
+        poll_fn_stream_raw(self, f)
     }
     pub fn subscribe<U>(
         &self,
-        mut f: impl FnMut(&mut St, &mut StateContext) -> U + 'static,
+        f: impl FnMut(&mut St, &mut StateContext) -> U + 'static,
     ) -> impl Stream<Item = U> + 'static
     where
-        St: Sync + Send + 'static,
+        St: 'static,
     {
-        struct WatchState {
-            waker: Option<Waker>,
-            age: usize,
-            is_dirty: bool,
-        }
-        let ws = WatchState {
-            waker: None,
-            age: 0,
-            is_dirty: true,
+        subscribe_raw(self, f)
+    }
+    /// Sets the [`TimerBackend`] used for [`StateContext::notify_at`] and deadlines of this container.
+    ///
+    /// Without it, the process-wide backend set by [`time::set_backend`](crate::time::set_backend) is used.
+    pub fn set_timer_backend(&self, backend: impl TimerBackend + 'static) {
+        self.lock_ignore_poison().g.set_timer_backend(backend);
+    }
+
+    /// Sets how this container behaves after it has been poisoned by a panic. The default is [`PoisonPolicy::Panic`].
+    ///
+    /// Setting [`PoisonPolicy::Recover`] on a container that is already poisoned recovers it on the next access.
+    pub fn set_poison_policy(&self, policy: PoisonPolicy) {
+        self.lock_ignore_poison().poison_policy = policy;
+    }
+
+    /// Returns a snapshot of the live states and the targets that depend on them, for debugging.
+    ///
+    /// This works even if the container has been poisoned.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        self.lock_ignore_poison().g.dependency_graph()
+    }
+
+    /// Returns the runtime statistics of this container.
+    ///
+    /// This works even if the container has been poisoned.
+    pub fn stats(&self) -> ContainerStats {
+        self.lock_ignore_poison().g.stats()
+    }
+
+    pub fn lock_untracked<'a>(&'a self) -> UntrackedState<'a, St> {
+        UntrackedState(
+            self.check_poison(self.lock())
+                .unwrap_or_else(|e| panic!("{e}")),
+        )
+    }
+
+    pub fn update<T>(&self, f: impl FnOnce(&mut St, &mut StateContext) -> T) -> T {
+        update_raw(self, f)
+    }
+
+    /// Like [`update`](Self::update), but returns an error without calling `f`
+    /// if the container is locked by someone else or has been poisoned.
+    pub fn try_update<T>(
+        &self,
+        f: impl FnOnce(&mut St, &mut StateContext) -> T,
+    ) -> Result<T, TryUpdateError> {
+        let _wakes;
+        let _span = trace_span!("update");
+        let mut ss = match self.0.try_lock() {
+            Ok(ss) => ss,
+            Err(TryLockError::WouldBlock) => return Err(TryUpdateError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => self.check_poison(Err(e))?,
         };
-        fn wake(ws: Arc<Mutex<WatchState>>, age: usize) {
-            let mut ws = ws.lock().unwrap();
-            if ws.age == age {
-                ws.is_dirty = true;
-                let waker = ws.waker.take();
-                if let Some(waker) = waker {
-                    drop(ws);
-                    waker.wake();
+        let ss = &mut *ss;
+        let value = f(&mut ss.st, ss.g.context());
+        _wakes = ss.g.take_deferred_wakes();
+        Ok(value)
+    }
+
+    /// Like [`update`](Self::update), but waits for the container lock without blocking the thread.
+    ///
+    /// While the lock is held by someone else, the task yields to the executor
+    /// and is woken when the lock is released.
+    pub async fn update_async<T>(&self, f: impl FnOnce(&mut St, &mut StateContext) -> T) -> T {
+        let _wakes;
+        let mut wait_start = None;
+        let mut ss = self
+            .check_poison(poll_fn(|cx| self.poll_lock(cx, &mut wait_start)).await)
+            .unwrap_or_else(|e| panic!("{e}"));
+        // The span is entered after the lock is acquired, because it must not be held across `await`.
+        let _span = trace_span!("update");
+        let ss = &mut *ss;
+        let value = f(&mut ss.st, ss.g.context());
+        _wakes = ss.g.take_deferred_wakes();
+        value
+    }
+
+    /// Updates the state with notifications deferred until the lock is released.
+    ///
+    /// Each dependent is woken at most once, after `f` returns and the lock is released,
+    /// no matter how many of its sources `f` has notified.
+    /// See [`StateContext::defer_notifications`].
+    pub fn batch<T>(&self, f: impl FnOnce(&mut St, &mut StateContext) -> T) -> T {
+        self.update(|st, cx| {
+            cx.defer_notifications();
+            f(st, cx)
+        })
+    }
+}
+
+impl<St> StateContainer<St> {
+    fn check_poison<'a>(
+        &self,
+        r: LockResult<AsyncMutexGuard<'a, RawStateContainer<St>>>,
+    ) -> Result<AsyncMutexGuard<'a, RawStateContainer<St>>, ContainerPoisoned> {
+        match r {
+            Ok(ss) => Ok(ss),
+            Err(e) => {
+                let mut ss = e.into_inner();
+                if ss.poison_policy != PoisonPolicy::Recover {
+                    return Err(ContainerPoisoned);
                 }
+                ss.g.recover();
+                self.0.clear_poison();
+                Ok(ss)
             }
         }
+    }
+    /// Locks the container, recording the time spent blocked if it is locked by someone else.
+    fn lock(&self) -> LockResult<AsyncMutexGuard<'_, RawStateContainer<St>>> {
+        let start = match self.0.try_lock() {
+            Ok(ss) => return Ok(ss),
+            Err(TryLockError::Poisoned(e)) => return Err(e),
+            Err(TryLockError::WouldBlock) => Instant::now(),
+        };
+        let mut r = self.0.lock();
+        let ss = match &mut r {
+            Ok(ss) => ss,
+            Err(e) => e.get_mut(),
+        };
+        ss.g.record_lock_wait(start.elapsed());
+        r
+    }
+    /// Locks the container without blocking the thread,
+    /// recording the time from the first `Poll::Pending`, kept in `wait_start`, until the lock is acquired.
+    fn poll_lock(
+        &self,
+        cx: &Context,
+        wait_start: &mut Option<Instant>,
+    ) -> Poll<LockResult<AsyncMutexGuard<'_, RawStateContainer<St>>>> {
+        let Poll::Ready(mut r) = self.0.poll_lock(cx) else {
+            wait_start.get_or_insert_with(Instant::now);
+            return Poll::Pending;
+        };
+        if let Some(start) = wait_start.take() {
+            let ss = match &mut r {
+                Ok(ss) => ss,
+                Err(e) => e.get_mut(),
+            };
+            ss.g.record_lock_wait(start.elapsed());
+        }
+        Poll::Ready(r)
+    }
+    fn lock_ignore_poison(&self) -> AsyncMutexGuard<'_, RawStateContainer<St>> {
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
 
-        let ws_arc = Arc::new(Mutex::new(ws));
-        let mut t = Target::new(self);
-        stream::poll_fn(move |cx| {
-            let st = &mut *t.st.0.lock().unwrap();
+pub(crate) fn update_raw<C: ContainerRef, T>(
+    c: &C,
+    f: impl FnOnce(&mut C::St, &mut StateContext) -> T,
+) -> T {
+    let _span = trace_span!("update");
+    // The wakers are called after `with` returns, so that they run after the lock is released.
+    let (value, _wakes) = c.with(|g, st| {
+        let value = f(st, g.context());
+        (value, g.take_deferred_wakes())
+    });
+    value
+}
+pub(crate) async fn poll_fn_raw<C: ContainerRef, U>(
+    c: &C,
+    mut f: impl FnMut(&mut C::St, &mut StateContext) -> Poll<U>,
+) -> U {
+    let mut t = Target::new(c);
+    poll_fn(|cx| t.poll_fn(&mut f, cx)).await
+}
+pub(crate) async fn poll_fn_until_raw<C: ContainerRef, U>(
+    c: &C,
+    deadline: AnyTime,
+    mut f: impl FnMut(&mut C::St, &mut StateContext) -> Poll<U>,
+) -> Result<U, Timeout> {
+    let mut wait_start = None;
+    let timer = poll_fn(|cx| c.poll_with(cx, &mut wait_start, |g, _| g.timer.clone())).await;
+    let mut t = Target::new(c);
+    let mut _task = None;
+    poll_fn(|cx| {
+        if let Poll::Ready(value) = t.poll_fn(&mut f, cx) {
+            return Poll::Ready(Ok(value));
+        }
+        if deadline.is_ready() {
+            return Poll::Ready(Err(Timeout));
+        }
+        _task = Some(spawn_at_with(timer.as_ref(), cx.waker().into(), deadline));
+        Poll::Pending
+    })
+    .await
+}
+pub(crate) fn poll_fn_stream_raw<C: ContainerRef + 'static, U>(
+    c: &C,
+    mut f: impl FnMut(&mut C::St, &mut StateContext) -> Poll<Option<U>> + 'static,
+) -> impl Stream<Item = U> + 'static {
+    let mut t = Target::new(c);
+    stream::poll_fn(move |cx| t.poll_fn(&mut f, cx))
+}
+pub(crate) fn subscribe_raw<C: ContainerRef + 'static, U>(
+    c: &C,
+    mut f: impl FnMut(&mut C::St, &mut StateContext) -> U + 'static,
+) -> impl Stream<Item = U> + 'static {
+    struct WatchState {
+        waker: Option<Waker>,
+        age: usize,
+        is_dirty: bool,
+    }
+    let ws = WatchState {
+        waker: None,
+        age: 0,
+        is_dirty: true,
+    };
+    fn wake(ws: Arc<Mutex<WatchState>>, age: usize) {
+        let mut ws = ws.lock().unwrap_or_else(PoisonError::into_inner);
+        if ws.age == age {
+            ws.is_dirty = true;
+            let waker = ws.waker.take();
+            if let Some(waker) = waker {
+                drop(ws);
+                waker.wake();
+            }
+        }
+    }
+
+    let ws_arc = Arc::new(Mutex::new(ws));
+    let mut t = Target::new(c);
+    let mut wake_at = None;
+    stream::poll_fn(move |cx| {
+        let (value, _wakes) = ready!(t.st.poll_with(cx, &mut t.lock_wait_start, |g, st| {
             let mut ws = ws_arc.lock().unwrap();
-            if ws.is_dirty {
-                if let Some(key) = t.key {
-                    st.g.remove_target(key);
-                    t.key = None;
-                }
-                st.g.source_set.clear();
-                t.sleep = None;
-                ws.age = ws.age.wrapping_add(1);
-                ws.is_dirty = false;
-                let value = f(&mut st.st, st.g.context());
-                (t.key, t.sleep) =
-                    st.g.commit_target(|| Action::from_arc_fn_usize(ws_arc.clone(), wake, ws.age));
-                Poll::Ready(Some(value))
-            } else {
+            if !ws.is_dirty {
                 ws.waker = Some(cx.waker().clone());
-                Poll::Pending
+                return (None, None);
             }
-        })
+            // The previous target is kept until `f` returns so that derived states
+            // recomputed by `f` can report whether they actually changed.
+            let old_key = t.key.take();
+            if let Some(key) = old_key {
+                g.disarm_target(key);
+            }
+            let is_timeout = wake_at.is_some_and(|at| crate::time::now() >= at);
+            g.source_set.clear();
+            t.sleep.take();
+            ws.age = ws.age.wrapping_add(1);
+            ws.is_dirty = false;
+            let _span = trace_span!("subscribe", target_id = old_key.map(|y| y.0));
+            let value = f(st, g.context());
+            let wakes = g.take_deferred_wakes();
+            g.commit_every(&mut t.ticker);
+            wake_at = g.wake_at;
+            let mut is_changed = true;
+            if let Some(key) = old_key {
+                is_changed = is_timeout || g.target_state(key) != TargetState::MaybeDirty;
+                g.remove_target(key);
+            }
+            (t.key, t.sleep) =
+                g.commit_target(|| Action::from_arc_fn_usize(ws_arc.clone(), wake, ws.age));
+            if !is_changed {
+                trace_event!("subscribe value unchanged");
+                ws.waker = Some(cx.waker().clone());
+            }
+            (is_changed.then_some(value), Some(wakes))
+        }));
+        match value {
+            Some(value) => Poll::Ready(Some(value)),
+            None => Poll::Pending,
+        }
+    })
+}
+
+/// The error returned when a wait with a deadline has timed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timeout;
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
     }
-    pub fn lock_untracked<'a>(&'a self) -> UntrackedState<'a, St> {
-        UntrackedState(self.0.lock().unwrap())
+}
+impl std::error::Error for Timeout {}
+
+/// The error returned when a [`StateContainer`] or [`RwStateContainer`](crate::RwStateContainer) has been poisoned
+/// by a panic inside one of its closures.
+///
+/// See [`PoisonPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContainerPoisoned;
+
+impl fmt::Display for ContainerPoisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "container is poisoned by a panic in another access")
     }
+}
+impl std::error::Error for ContainerPoisoned {}
 
-    pub fn update<T>(&self, f: impl FnOnce(&mut St, &mut StateContext) -> T) -> T {
-        let ss = &mut *self.0.lock().unwrap();
-        f(&mut ss.st, ss.g.context())
+/// The error returned by [`StateContainer::try_update`] and [`RwStateContainer::try_update`](crate::RwStateContainer::try_update).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TryUpdateError {
+    /// The container is locked by someone else.
+    WouldBlock,
+    /// The container has been poisoned.
+    Poisoned(ContainerPoisoned),
+}
+
+impl From<ContainerPoisoned> for TryUpdateError {
+    fn from(e: ContainerPoisoned) -> Self {
+        Self::Poisoned(e)
     }
 }
+impl fmt::Display for TryUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WouldBlock => write!(f, "container is locked"),
+            Self::Poisoned(e) => e.fmt(f),
+        }
+    }
+}
+impl std::error::Error for TryUpdateError {}
 
-pub struct UntrackedState<'a, St>(MutexGuard<'a, RawStateContainer<St>>);
+pub struct UntrackedState<'a, St>(AsyncMutexGuard<'a, RawStateContainer<St>>);
 impl<'a, St> std::ops::Deref for UntrackedState<'a, St> {
     type Target = St;
     fn deref(&self) -> &Self::Target {
@@ -222,44 +996,87 @@ impl<'a, St> std::ops::Deref for UntrackedState<'a, St> {
     }
 }
 
-struct Target<St> {
+pub(crate) struct Target<C: ContainerRef> {
     key: Option<YKey>,
-    st: StateContainer<St>,
+    st: C,
     sleep: Option<SpawnAtTask>,
+    ticker: Option<Ticker>,
+    lock_wait_start: Option<Instant>,
 }
-impl<St> Target<St> {
-    pub fn new(st: &StateContainer<St>) -> Self {
+impl<C: ContainerRef> Target<C> {
+    pub fn new(st: &C) -> Self {
         Self {
             key: None,
             st: st.clone(),
             sleep: None,
+            ticker: None,
+            lock_wait_start: None,
         }
     }
-    fn poll_fn<T>(
+    /// Runs `f` and arms `waker` to be called when the states read by `f` change.
+    ///
+    /// Returns `Poll::Pending` without calling `f` if the container is locked, and wakes `cx` when it is released.
+    pub fn poll_run<T, A: Into<Action>>(
+        &mut self,
+        cx: &Context,
+        f: impl FnOnce(&mut C::St, &mut StateContext) -> T,
+        waker: impl Fn() -> A,
+    ) -> Poll<T> {
+        let (value, _wakes) = ready!(self.st.poll_with(cx, &mut self.lock_wait_start, |g, st| {
+            if let Some(y) = self.key.take() {
+                g.remove_target(y);
+            }
+            g.source_set.clear();
+            self.sleep.take();
+            let value = f(st, g.context());
+            let wakes = g.take_deferred_wakes();
+            g.commit_every(&mut self.ticker);
+            (self.key, self.sleep) = g.commit_target(waker);
+            (value, wakes)
+        }));
+        Poll::Ready(value)
+    }
+    pub fn poll_fn<T>(
         &mut self,
-        mut f: impl FnMut(&mut St, &mut StateContext) -> Poll<T>,
+        f: impl FnMut(&mut C::St, &mut StateContext) -> Poll<T>,
         cx: &mut Context,
     ) -> Poll<T> {
-        let st = &mut *self.st.0.lock().unwrap();
-        if let Some(y) = self.key.take() {
-            st.g.remove_target(y);
-        }
-        st.g.source_set.clear();
-        self.sleep = None;
-        match f(&mut st.st, st.g.context()) {
-            Poll::Ready(value) => Poll::Ready(value),
-            Poll::Pending => {
-                (self.key, self.sleep) = st.g.commit_target(|| cx.waker());
-                Poll::Pending
+        self.try_poll_fn(f, cx)
+            .map(|r| r.unwrap_or_else(|e| panic!("{e}")))
+    }
+    pub fn try_poll_fn<T>(
+        &mut self,
+        mut f: impl FnMut(&mut C::St, &mut StateContext) -> Poll<T>,
+        cx: &mut Context,
+    ) -> Poll<Result<T, ContainerPoisoned>> {
+        let (value, _wakes) = ready!(self.st.try_poll_with(
+            cx,
+            &mut self.lock_wait_start,
+            |g, st| {
+                let _span = trace_span!("poll_fn", target_id = self.key.map(|y| y.0));
+                if let Some(y) = self.key.take() {
+                    g.remove_target(y);
+                }
+                g.source_set.clear();
+                self.sleep.take();
+                let value = f(st, g.context());
+                let wakes = g.take_deferred_wakes();
+                if value.is_pending() {
+                    g.commit_every(&mut self.ticker);
+                    (self.key, self.sleep) = g.commit_target(|| cx.waker());
+                }
+                (value, wakes)
             }
-        }
+        )?);
+        value.map(Ok)
     }
 }
 
-impl<St> Drop for Target<St> {
+impl<C: ContainerRef> Drop for Target<C> {
     fn drop(&mut self) {
         if let Some(key) = self.key.take() {
-            self.st.0.lock().unwrap().g.remove_target(key);
+            // Ignore poisoning so that dropping a target while unwinding does not panic again.
+            self.st.try_with(|g, _| g.remove_target(key)).ok();
         }
     }
 }
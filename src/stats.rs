@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+/// Runtime statistics of a container, returned by [`StateContainer::stats`](crate::StateContainer::stats).
+///
+/// The counters are cumulative since the container was created.
+/// Rates such as notifications per second can be computed from the difference between two snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ContainerStats {
+    /// The number of live [`StateKey`](crate::StateKey)s.
+    pub keys: usize,
+    /// The number of targets waiting for notifications, including those of effects and memos.
+    pub targets: usize,
+    /// The number of dependencies from targets to keys.
+    pub edges: usize,
+    /// The number of calls to [`StateKey::notify`](crate::StateKey::notify).
+    pub notifications: u64,
+    /// The number of wakers called because of notifications.
+    pub wakeups: u64,
+    /// The number of times a thread or task had to wait because the container was locked.
+    pub lock_contentions: u64,
+    /// The total time threads and tasks have spent waiting for the container lock.
+    ///
+    /// For a task waiting without blocking the thread, this is the time from the first poll that found the container locked
+    /// until the lock was acquired.
+    pub lock_wait: Duration,
+}
+
+#[cfg(feature = "metrics")]
+impl ContainerStats {
+    /// Records the statistics to the [`metrics`] facade, with a `container` label set to `container`.
+    ///
+    /// Counts of live items are recorded as gauges, and cumulative counters as counters with `_total` names.
+    /// The lock wait time is recorded in microseconds.
+    pub fn record_metrics(&self, container: &str) {
+        let labels = [("container", container.to_string())];
+        metrics::gauge!("sigwake_keys", &labels).set(self.keys as f64);
+        metrics::gauge!("sigwake_targets", &labels).set(self.targets as f64);
+        metrics::gauge!("sigwake_edges", &labels).set(self.edges as f64);
+        metrics::counter!("sigwake_notifications_total", &labels).absolute(self.notifications);
+        metrics::counter!("sigwake_wakeups_total", &labels).absolute(self.wakeups);
+        metrics::counter!("sigwake_lock_contentions_total", &labels)
+            .absolute(self.lock_contentions);
+        metrics::counter!("sigwake_lock_wait_microseconds_total", &labels)
+            .absolute(self.lock_wait.as_micros() as u64);
+    }
+}
@@ -1,25 +1,109 @@
 use std::{
+    array,
     future::poll_fn,
-    sync::{Condvar, LazyLock, Mutex},
-    task::Poll,
-    thread::spawn,
+    mem,
+    pin::Pin,
+    sync::{
+        Arc, Condvar, LazyLock, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    thread::{self, ThreadId, spawn},
     time::{Duration, Instant, SystemTime},
 };
 
+use derive_ex::Ex;
+use futures::Stream;
+
+use crate::Timeout;
 use crate::utils::Action;
 use crate::utils::btree_multi_map::BTreeMultiMap;
+use crate::utils::timer_wheel::{TimerWheel, TimerWheelKey};
+
+#[cfg(feature = "tokio")]
+mod tokio_timer;
+
+#[cfg(feature = "tokio")]
+pub use tokio_timer::TokioTimer;
 
+/// A handle to an action scheduled by [`spawn_at`]. Dropping it cancels the action.
 #[derive(Debug)]
-pub struct SpawnAtTask {
-    at: RawAnyTime,
-    id: usize,
+pub struct SpawnAtTask(RawSpawnAtTask);
+
+#[derive(Debug)]
+enum RawSpawnAtTask {
+    Instant { shard: usize, key: TimerWheelKey },
+    SystemTime { at: SystemTime, id: usize },
+    Cancel(Option<Action>),
+}
+impl SpawnAtTask {
+    /// Creates a handle for an action scheduled by a custom [`TimerBackend`].
+    ///
+    /// `cancel` is called when the handle is dropped, and should cancel the action if it has not been called yet.
+    pub fn from_cancel(cancel: impl Into<Action>) -> Self {
+        Self(RawSpawnAtTask::Cancel(Some(cancel.into())))
+    }
 }
 impl Drop for SpawnAtTask {
     fn drop(&mut self) {
-        TIMER.cancel(self);
+        match &mut self.0 {
+            RawSpawnAtTask::Cancel(cancel) => {
+                if let Some(cancel) = cancel.take() {
+                    cancel.call();
+                }
+            }
+            task => TIMER.cancel(task),
+        }
     }
 }
 
+/// A mechanism that calls actions at specified times.
+///
+/// By default, actions are called on a dedicated timer thread ([`ThreadTimer`]).
+/// Applications that already run a timer, such as the one of an async runtime,
+/// can use it instead with [`set_backend`] or [`StateContainer::set_timer_backend`](crate::StateContainer::set_timer_backend).
+pub trait TimerBackend: Send + Sync {
+    /// Calls `action` at `at`, unless the returned handle is dropped before that.
+    fn spawn_at(&self, action: Action, at: AnyTime) -> SpawnAtTask;
+}
+
+/// The default [`TimerBackend`], which calls actions on a dedicated thread.
+///
+/// The thread is started when needed and exits when no action has been scheduled for a while.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadTimer;
+
+impl TimerBackend for ThreadTimer {
+    fn spawn_at(&self, action: Action, at: AnyTime) -> SpawnAtTask {
+        TIMER.spawn_at(action, at.0)
+    }
+}
+
+static BACKEND: RwLock<Option<Arc<dyn TimerBackend>>> = RwLock::new(None);
+
+/// Sets the process-wide [`TimerBackend`] used by [`spawn_at`], [`sleep`] and state containers without their own backend.
+///
+/// While a [`ManualClock`] is installed, it takes precedence over any backend.
+pub fn set_backend(backend: impl TimerBackend + 'static) {
+    *BACKEND.write().unwrap() = Some(Arc::new(backend));
+}
+
+pub(crate) fn spawn_at_with(
+    backend: Option<&Arc<dyn TimerBackend>>,
+    action: Action,
+    at: AnyTime,
+) -> SpawnAtTask {
+    if !is_manual() {
+        if let Some(backend) = backend {
+            return backend.spawn_at(action, at);
+        }
+        if let Some(backend) = &*BACKEND.read().unwrap() {
+            return backend.spawn_at(action, at);
+        }
+    }
+    TIMER.spawn_at(action, at.0)
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct AnyTime(RawAnyTime);
 
@@ -31,8 +115,33 @@ enum RawAnyTime {
 impl RawAnyTime {
     fn is_ready(&self) -> bool {
         match self {
-            RawAnyTime::Instant(instant) => Instant::now() >= *instant,
-            RawAnyTime::SystemTime(system_time) => SystemTime::now() >= *system_time,
+            RawAnyTime::Instant(instant) => now() >= *instant,
+            RawAnyTime::SystemTime(system_time) => system_now() >= *system_time,
+        }
+    }
+}
+
+impl AnyTime {
+    /// Returns `true` if the time has been reached according to [`now`] or [`system_now`].
+    pub fn is_ready(&self) -> bool {
+        self.0.is_ready()
+    }
+
+    /// Returns the time as an [`Instant`] if it was created from an `Instant` or a `Duration`.
+    pub fn as_instant(&self) -> Option<Instant> {
+        match self.0 {
+            RawAnyTime::Instant(instant) => Some(instant),
+            RawAnyTime::SystemTime(_) => None,
+        }
+    }
+
+    /// Returns the time remaining until the time is reached, or zero if it has already been reached.
+    pub fn remaining(&self) -> Duration {
+        match self.0 {
+            RawAnyTime::Instant(instant) => instant.saturating_duration_since(now()),
+            RawAnyTime::SystemTime(system_time) => {
+                system_time.duration_since(system_now()).unwrap_or_default()
+            }
         }
     }
 }
@@ -49,7 +158,7 @@ impl From<SystemTime> for AnyTime {
 }
 impl From<Duration> for AnyTime {
     fn from(value: Duration) -> Self {
-        Self(RawAnyTime::Instant(Instant::now() + value))
+        Self(RawAnyTime::Instant(now() + value))
     }
 }
 
@@ -57,7 +166,7 @@ pub fn spawn_at(action: impl Into<Action>, at: impl Into<AnyTime>) -> SpawnAtTas
     spawn_at_raw(action.into(), at.into())
 }
 fn spawn_at_raw(action: Action, at: AnyTime) -> SpawnAtTask {
-    TIMER.spawn_at(action, at.0)
+    spawn_at_with(None, action, at)
 }
 
 pub async fn sleep(time: impl Into<AnyTime>) {
@@ -74,134 +183,620 @@ pub async fn sleep(time: impl Into<AnyTime>) {
     .await
 }
 
-static TIMER: LazyLock<Timer> = LazyLock::new(Timer::new);
-static THREAD_CACHE_DURATION: Duration = Duration::from_secs(4);
+/// How periodic timers behave when ticks are missed because they were observed too late.
+///
+/// A tick is missed when the next tick is already due by the time the previous one is observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MissedTickBehavior {
+    /// Ticks as fast as possible until it catches up with the original schedule.
+    #[default]
+    Burst,
+    /// Schedules the next tick one period after the time the late tick was observed.
+    Delay,
+    /// Skips the missed ticks and continues with the next tick of the original schedule.
+    Skip,
+}
+impl MissedTickBehavior {
+    pub(crate) fn next_tick(self, tick: Instant, period: Duration, now: Instant) -> Instant {
+        let next = tick + period;
+        if next > now {
+            return next;
+        }
+        match self {
+            Self::Burst => next,
+            Self::Delay => now + period,
+            Self::Skip => {
+                let missed = (now - tick).as_nanos() / period.as_nanos();
+                tick + Duration::from_nanos((period.as_nanos() * (missed + 1)) as u64)
+            }
+        }
+    }
+}
 
-struct TimerData {
-    actions_instant: BTreeMultiMap<Instant, Action>,
-    actions_system_time: BTreeMultiMap<SystemTime, Action>,
-    is_running: bool,
+/// Creates a stream that yields the time of each tick, starting immediately and then every `period`.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    interval_at(now(), period)
 }
-impl TimerData {
-    const fn new() -> Self {
-        Self {
-            actions_instant: BTreeMultiMap::new(),
-            actions_system_time: BTreeMultiMap::new(),
-            is_running: false,
+
+/// Creates a stream that yields the time of each tick, starting at `start` and then every `period`.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(!period.is_zero(), "`period` must be non-zero");
+    Interval {
+        next: start,
+        period,
+        missed_tick_behavior: MissedTickBehavior::default(),
+        task: None,
+    }
+}
+
+/// A stream returned by [`interval`] and [`interval_at`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct Interval {
+    next: Instant,
+    period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+    task: Option<SpawnAtTask>,
+}
+impl Interval {
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+
+    /// Restarts the schedule so that the next tick is one period from now.
+    pub fn reset(&mut self) {
+        self.next = now() + self.period;
+        self.task = None;
+    }
+}
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let now = now();
+        if now >= this.next {
+            let tick = this.next;
+            this.next = this.missed_tick_behavior.next_tick(tick, this.period, now);
+            this.task = None;
+            return Poll::Ready(Some(tick));
         }
+        this.task = Some(spawn_at(cx.waker(), this.next));
+        Poll::Pending
     }
+}
 
-    fn step(&mut self) -> TimerStep {
-        let mut next = Duration::MAX;
-        if let Some(e) = self.actions_instant.first_entry() {
-            let now = Instant::now();
-            let key = e.key().0;
-            if now >= key {
-                return TimerStep::Call(e.remove());
+/// Calls `f` every `period`, starting one period from now, until the returned handle is dropped.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn spawn_every(
+    f: impl FnMut() + Send + 'static,
+    period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+) -> SpawnEveryTask {
+    assert!(!period.is_zero(), "`period` must be non-zero");
+    let every = Arc::new(Every {
+        f: Mutex::new(Box::new(f)),
+        period,
+        missed_tick_behavior,
+        state: Mutex::new(EveryState {
+            next: now() + period,
+            task: None,
+            is_cancelled: false,
+            calling: None,
+        }),
+    });
+    every.schedule(&mut every.state.lock().unwrap());
+    SpawnEveryTask(every)
+}
+
+/// A handle to an action scheduled by [`spawn_every`]. Dropping it stops the action.
+#[derive(Debug)]
+pub struct SpawnEveryTask(Arc<Every>);
+
+impl Drop for SpawnEveryTask {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.is_cancelled = true;
+        let task = state.task.take();
+        let is_calling = state.calling == Some(thread::current().id());
+        drop(state);
+        drop(task);
+
+        // Wait for a call in progress on another thread, so that `f` is never called after the handle is dropped.
+        // The handle may be dropped inside `f` itself, which must not wait for its own call.
+        if !is_calling {
+            drop(self.0.f.lock());
+        }
+    }
+}
+
+#[derive(Ex)]
+#[derive_ex(Debug)]
+struct Every {
+    #[debug(ignore)]
+    f: Mutex<Box<dyn FnMut() + Send>>,
+    period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+    state: Mutex<EveryState>,
+}
+
+#[derive(Debug)]
+struct EveryState {
+    next: Instant,
+    task: Option<SpawnAtTask>,
+    is_cancelled: bool,
+    /// The thread that is calling `f`.
+    calling: Option<ThreadId>,
+}
+
+impl Every {
+    fn schedule(self: &Arc<Self>, state: &mut EveryState) {
+        let this = self.clone();
+        state.task = Some(spawn_at_raw(
+            Action::new(move || this.tick()),
+            state.next.into(),
+        ));
+    }
+    fn tick(self: Arc<Self>) {
+        let mut f = self.f.lock().unwrap();
+        {
+            // Checked after locking `f`, so that a handle dropped while waiting for the lock stops the call.
+            let mut state = self.state.lock().unwrap();
+            if state.is_cancelled {
+                return;
             }
-            next = key - now;
+            state.calling = Some(thread::current().id());
         }
-        if let Some(e) = self.actions_system_time.first_entry() {
-            let now = SystemTime::now();
-            let key = e.key().0;
-            if now >= key {
-                return TimerStep::Call(e.remove());
+        f();
+        drop(f);
+        let mut state = self.state.lock().unwrap();
+        state.calling = None;
+        if state.is_cancelled {
+            return;
+        }
+        state.next = self
+            .missed_tick_behavior
+            .next_tick(state.next, self.period, now());
+        let task = state.task.take();
+        self.schedule(&mut state);
+        drop(state);
+        drop(task);
+    }
+}
+
+/// Limits a stream to items that arrive before `deadline`.
+///
+/// The returned stream yields `Ok` for each item of `s`.
+/// When `deadline` is reached before `s` ends, it yields `Err(Timeout)` once and then ends.
+pub fn stream_until<S: Stream>(s: S, deadline: impl Into<AnyTime>) -> StreamUntil<S> {
+    StreamUntil {
+        s: Some(Box::pin(s)),
+        deadline: deadline.into(),
+        task: None,
+    }
+}
+
+/// A stream returned by [`stream_until`].
+#[must_use = "streams do nothing unless polled"]
+pub struct StreamUntil<S> {
+    s: Option<Pin<Box<S>>>,
+    deadline: AnyTime,
+    task: Option<SpawnAtTask>,
+}
+impl<S: Stream> Stream for StreamUntil<S> {
+    type Item = Result<S::Item, Timeout>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(s) = &mut this.s else {
+            return Poll::Ready(None);
+        };
+        if let Poll::Ready(item) = s.as_mut().poll_next(cx) {
+            if item.is_none() {
+                this.s = None;
+                this.task = None;
             }
-            if let Ok(next_new) = key.duration_since(now) {
-                next = next.min(next_new);
+            return Poll::Ready(item.map(Ok));
+        }
+        if this.deadline.is_ready() {
+            this.s = None;
+            this.task = None;
+            return Poll::Ready(Some(Err(Timeout)));
+        }
+        this.task = Some(spawn_at(cx.waker(), this.deadline));
+        Poll::Pending
+    }
+}
+
+/// Returns the current time of the clock used by timers in this crate.
+///
+/// This is [`Instant::now`] unless a [`ManualClock`] is installed.
+pub fn now() -> Instant {
+    if is_manual() {
+        if let Some(t) = *MANUAL_TIME.lock().unwrap() {
+            return t.instant;
+        }
+    }
+    Instant::now()
+}
+
+/// Returns the current system time of the clock used by timers in this crate.
+///
+/// This is [`SystemTime::now`] unless a [`ManualClock`] is installed.
+pub fn system_now() -> SystemTime {
+    if is_manual() {
+        if let Some(t) = *MANUAL_TIME.lock().unwrap() {
+            return t.system_time;
+        }
+    }
+    SystemTime::now()
+}
+
+/// Runtime statistics of the built-in timer, returned by [`stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimerStats {
+    /// The number of actions waiting to be called.
+    pub pending: usize,
+    /// The number of times the timer thread has been started since the process started.
+    pub thread_spawns: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl TimerStats {
+    /// Records the statistics to the [`metrics`] facade.
+    pub fn record_metrics(&self) {
+        metrics::gauge!("sigwake_timer_pending").set(self.pending as f64);
+        metrics::counter!("sigwake_timer_thread_spawns_total").absolute(self.thread_spawns);
+    }
+}
+
+/// Returns the runtime statistics of the built-in timer used by [`ThreadTimer`] and [`ManualClock`].
+///
+/// Actions scheduled on other [`TimerBackend`]s are not included.
+pub fn stats() -> TimerStats {
+    TIMER.stats()
+}
+
+static IS_MANUAL: AtomicBool = AtomicBool::new(false);
+static MANUAL_TIME: Mutex<Option<ManualTime>> = Mutex::new(None);
+
+fn is_manual() -> bool {
+    IS_MANUAL.load(Ordering::Acquire)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ManualTime {
+    instant: Instant,
+    system_time: SystemTime,
+}
+
+/// A process-wide clock that only advances when told to, for deterministic tests of time-based states.
+///
+/// While a `ManualClock` is installed, [`now`], [`system_now`], [`sleep`], [`spawn_at`] and
+/// [`StateContext::notify_at`](crate::StateContext::notify_at) follow this clock instead of the real one,
+/// and actions that become due are called on the thread that advances the clock.
+///
+/// Because the clock is shared by the whole process, tests that install it should be placed in their own test binary.
+/// Dropping the `ManualClock` switches back to the real clock.
+#[derive(Debug)]
+pub struct ManualClock {
+    _private: (),
+}
+impl ManualClock {
+    /// Installs a manual clock starting at the current time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a `ManualClock` is already installed.
+    pub fn install() -> Self {
+        let mut t = MANUAL_TIME.lock().unwrap();
+        assert!(t.is_none(), "ManualClock is already installed");
+        // Start at a tick boundary so that whole-millisecond durations land exactly on ticks.
+        let now = Instant::now();
+        let instant = TIMER.instant(TIMER.tick_ceil(now)).unwrap_or(now);
+        *t = Some(ManualTime {
+            instant,
+            system_time: SystemTime::now(),
+        });
+        IS_MANUAL.store(true, Ordering::Release);
+        TIMER.reset(instant);
+        TIMER.notify(&mut TIMER.state.lock().unwrap());
+        Self { _private: () }
+    }
+
+    /// Advances both the monotonic clock and the system clock by `duration`.
+    ///
+    /// Actions that become due are called in the order of their times,
+    /// with the clock set to the time of each action while it is called.
+    pub fn advance(&self, duration: Duration) {
+        let t = Self::time();
+        let end = t.instant + duration;
+        let end_system_time = t.system_time + duration;
+        let mut actions = Vec::new();
+        loop {
+            let (next, next_system_time) = TIMER.poll(&mut actions);
+            if !actions.is_empty() {
+                for action in actions.drain(..) {
+                    action.call();
+                }
+                continue;
+            }
+            let t = Self::time();
+            let mut d = None;
+            if let Some(at) = next.and_then(|tick| TIMER.instant(tick)) {
+                if at <= end {
+                    d = Some(at.saturating_duration_since(t.instant));
+                }
+            }
+            if let Some(at) = next_system_time {
+                if at <= end_system_time {
+                    let d_system = at.duration_since(t.system_time).unwrap_or_default();
+                    d = Some(d.map_or(d_system, |d: Duration| d.min(d_system)));
+                }
             }
-            next = next.min(Duration::from_secs(1));
+            let Some(d) = d else {
+                Self::set_time(end, end_system_time);
+                return;
+            };
+            Self::set_time(t.instant + d, t.system_time + d);
         }
-        if next == Duration::MAX {
-            TimerStep::None
-        } else {
-            TimerStep::Wait(next)
+    }
+
+    /// Sets the system clock, which may go backwards, and calls the actions that have become due.
+    pub fn set_system_time(&self, system_time: SystemTime) {
+        Self::set_time(Self::time().instant, system_time);
+        let mut actions = Vec::new();
+        loop {
+            TIMER.poll(&mut actions);
+            if actions.is_empty() {
+                return;
+            }
+            for action in actions.drain(..) {
+                action.call();
+            }
+        }
+    }
+
+    fn time() -> ManualTime {
+        MANUAL_TIME.lock().unwrap().unwrap()
+    }
+    fn set_time(instant: Instant, system_time: SystemTime) {
+        *MANUAL_TIME.lock().unwrap() = Some(ManualTime {
+            instant,
+            system_time,
+        });
+    }
+}
+impl Drop for ManualClock {
+    fn drop(&mut self) {
+        *MANUAL_TIME.lock().unwrap() = None;
+        IS_MANUAL.store(false, Ordering::Release);
+        TIMER.reset(Instant::now());
+        if !TIMER.is_empty() {
+            TIMER.start_or_notify();
         }
     }
 }
 
-enum TimerStep {
-    None,
-    Call(Action),
-    Wait(Duration),
+static TIMER: LazyLock<Timer> = LazyLock::new(Timer::new);
+static THREAD_CACHE_DURATION: Duration = Duration::from_secs(4);
+
+/// The number of timer wheels, each with its own lock, so that threads scheduling actions rarely contend.
+const SHARDS: usize = 16;
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
 }
 
+/// The built-in timer.
+///
+/// Actions at an [`Instant`] are kept in timer wheels with a resolution of 1 ms, so they may be called up to 1 ms late.
+/// Actions at a [`SystemTime`] are kept in a sorted map, because the system clock may jump.
 struct Timer {
-    data: Mutex<TimerData>,
+    start: Instant,
+    shards: [Mutex<TimerWheel<Action>>; SHARDS],
+    actions_system_time: Mutex<BTreeMultiMap<SystemTime, Action>>,
+    /// The tick at which the timer thread will wake, or `u64::MAX` if it must be notified of every new action.
+    next_wake: AtomicU64,
+    thread_spawns: AtomicU64,
+    state: Mutex<TimerState>,
     cvar: Condvar,
 }
+struct TimerState {
+    is_running: bool,
+    is_notified: bool,
+}
+
 impl Timer {
-    const fn new() -> Self {
+    fn new() -> Self {
         Self {
-            data: Mutex::new(TimerData::new()),
+            start: Instant::now(),
+            shards: array::from_fn(|_| Mutex::new(TimerWheel::new())),
+            actions_system_time: Mutex::new(BTreeMultiMap::new()),
+            next_wake: AtomicU64::new(u64::MAX),
+            thread_spawns: AtomicU64::new(0),
+            state: Mutex::new(TimerState {
+                is_running: false,
+                is_notified: false,
+            }),
             cvar: Condvar::new(),
         }
     }
-}
-impl Timer {
+    fn tick_ceil(&self, at: Instant) -> u64 {
+        let nanos = at.saturating_duration_since(self.start).as_nanos();
+        u64::try_from(nanos.div_ceil(1_000_000)).unwrap_or(u64::MAX)
+    }
+    fn tick_floor(&self, at: Instant) -> u64 {
+        u64::try_from(at.saturating_duration_since(self.start).as_millis()).unwrap_or(u64::MAX)
+    }
+    fn instant(&self, tick: u64) -> Option<Instant> {
+        self.start.checked_add(Duration::from_millis(tick))
+    }
+    /// Moves the current tick of every wheel to `now`, which is needed when switching between the real and a manual clock.
+    fn reset(&self, now: Instant) {
+        let now = self.tick_floor(now);
+        for shard in &self.shards {
+            shard.lock().unwrap().reset(now);
+        }
+    }
+    fn stats(&self) -> TimerStats {
+        let pending = self
+            .shards
+            .iter()
+            .map(|s| s.lock().unwrap().len())
+            .sum::<usize>()
+            + self.actions_system_time.lock().unwrap().len();
+        TimerStats {
+            pending,
+            thread_spawns: self.thread_spawns.load(Ordering::Relaxed),
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.lock().unwrap().is_empty())
+            && self.actions_system_time.lock().unwrap().is_empty()
+    }
+
     fn spawn_at(&self, action: Action, at: RawAnyTime) -> SpawnAtTask {
-        let mut data = self.data.lock().unwrap();
-        let is_wake;
-        let id;
         match at {
             RawAnyTime::Instant(at) => {
-                is_wake = is_wake_with(&data.actions_instant, at);
-                id = data.actions_instant.insert(at, action);
+                let tick = self.tick_ceil(at);
+                let shard = SHARD.try_with(|shard| *shard).unwrap_or(0);
+                let key = self.shards[shard].lock().unwrap().insert(tick, action);
+                if tick < self.next_wake.load(Ordering::SeqCst) {
+                    self.start_or_notify();
+                }
+                SpawnAtTask(RawSpawnAtTask::Instant { shard, key })
             }
             RawAnyTime::SystemTime(at) => {
-                is_wake = is_wake_with(&data.actions_system_time, at);
-                id = data.actions_system_time.insert(at, action);
+                let mut actions = self.actions_system_time.lock().unwrap();
+                let is_wake = is_wake_with(&actions, at);
+                let id = actions.insert(at, action);
+                drop(actions);
+                if is_wake {
+                    self.start_or_notify();
+                }
+                SpawnAtTask(RawSpawnAtTask::SystemTime { at, id })
             }
         }
-        if is_wake {
-            if data.is_running {
-                self.cvar.notify_one();
-            } else {
-                data.is_running = true;
-                spawn(|| TIMER.run());
+    }
+    fn start_or_notify(&self) {
+        if is_manual() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.is_running {
+            self.notify(&mut state);
+        } else {
+            state.is_running = true;
+            self.thread_spawns.fetch_add(1, Ordering::Relaxed);
+            spawn(|| TIMER.run());
+        }
+    }
+    fn notify(&self, state: &mut TimerState) {
+        state.is_notified = true;
+        self.cvar.notify_one();
+    }
+    fn cancel(&self, task: &RawSpawnAtTask) {
+        match task {
+            RawSpawnAtTask::Instant { shard, key } => {
+                self.shards[*shard].lock().unwrap().remove(*key);
             }
+            RawSpawnAtTask::SystemTime { at, id } => {
+                self.actions_system_time.lock().unwrap().remove(*at, *id);
+            }
+            RawSpawnAtTask::Cancel(_) => unreachable!(),
         }
-        SpawnAtTask { at, id }
     }
-    fn cancel(&self, task: &SpawnAtTask) {
-        let mut data = self.data.lock().unwrap();
-        match task.at {
-            RawAnyTime::Instant(at) => {
-                data.actions_instant.remove(at, task.id);
+
+    /// Moves the actions that are due into `actions`.
+    ///
+    /// Returns the tick at which actions at an `Instant` need to be polled again, and the time of the next action at a `SystemTime`.
+    fn poll(&self, actions: &mut Vec<Action>) -> (Option<u64>, Option<SystemTime>) {
+        let now = self.tick_floor(now());
+        let mut next: Option<u64> = None;
+        for shard in &self.shards {
+            let mut wheel = shard.lock().unwrap();
+            while let Some(action) = wheel.poll(now) {
+                actions.push(action);
             }
-            RawAnyTime::SystemTime(at) => {
-                data.actions_system_time.remove(at, task.id);
+            if let Some(tick) = wheel.next_expiration() {
+                next = Some(next.map_or(tick, |next| next.min(tick)));
             }
         }
-        if data.actions_instant.is_empty() && data.actions_system_time.is_empty() {
-            self.cvar.notify_one();
+        let now = system_now();
+        let mut actions_system_time = self.actions_system_time.lock().unwrap();
+        while let Some(e) = actions_system_time.first_entry() {
+            if e.key().0 > now {
+                break;
+            }
+            actions.push(e.remove());
         }
+        (next, actions_system_time.first_key().copied())
     }
     fn run(&self) {
-        let mut used = false;
+        let mut actions = Vec::new();
         let mut last_used = Instant::now();
         loop {
-            let mut data = self.data.lock().unwrap();
-            match data.step() {
-                TimerStep::None => {
-                    let now = Instant::now();
-                    if used {
-                        last_used = now;
-                    } else if now.duration_since(last_used) >= THREAD_CACHE_DURATION {
-                        data.is_running = false;
-                        return;
-                    }
-                    used = self.cvar.wait_timeout(data, THREAD_CACHE_DURATION).is_ok();
-                }
-                TimerStep::Call(action) => {
-                    drop(data);
+            self.next_wake.store(u64::MAX, Ordering::SeqCst);
+            let (next, next_system_time) = self.poll(&mut actions);
+            if !actions.is_empty() {
+                for action in actions.drain(..) {
                     action.call();
-                    used = true;
-                }
-                TimerStep::Wait(dur) => {
-                    used |= self.cvar.wait_timeout(data, dur).is_ok();
                 }
+                last_used = Instant::now();
+                continue;
+            }
+            let mut state = self.state.lock().unwrap();
+            if is_manual() {
+                state.is_running = false;
+                return;
             }
+            if mem::take(&mut state.is_notified) {
+                continue;
+            }
+            let now = Instant::now();
+            let mut wait = None;
+            if let Some(tick) = next {
+                wait = Some(self.instant(tick).map_or(Duration::from_secs(3600), |at| {
+                    at.saturating_duration_since(now)
+                }));
+            }
+            if let Some(at) = next_system_time {
+                let d = at.duration_since(SystemTime::now()).unwrap_or_default();
+                let d = d.min(Duration::from_secs(1));
+                wait = Some(wait.map_or(d, |wait: Duration| wait.min(d)));
+            }
+            let wait = if let Some(wait) = wait {
+                last_used = now;
+                wait
+            } else if now.duration_since(last_used) >= THREAD_CACHE_DURATION {
+                state.is_running = false;
+                return;
+            } else {
+                THREAD_CACHE_DURATION
+            };
+            self.next_wake
+                .store(self.tick_floor(now + wait), Ordering::SeqCst);
+            drop(self.cvar.wait_timeout(state, wait));
         }
     }
 }
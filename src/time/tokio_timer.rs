@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+
+use crate::{
+    time::{AnyTime, SpawnAtTask, TimerBackend},
+    utils::Action,
+};
+
+/// A [`TimerBackend`] that calls actions from tasks spawned on a tokio runtime.
+///
+/// Actions that have not been called yet are dropped when the runtime shuts down.
+#[derive(Debug, Clone)]
+pub struct TokioTimer(Handle);
+
+impl TokioTimer {
+    /// Creates a backend that uses the runtime of the current context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn current() -> Self {
+        Self(Handle::current())
+    }
+
+    /// Creates a backend that uses the specified runtime.
+    pub fn with_handle(handle: Handle) -> Self {
+        Self(handle)
+    }
+}
+
+impl TimerBackend for TokioTimer {
+    fn spawn_at(&self, action: Action, at: AnyTime) -> SpawnAtTask {
+        let task = self.0.spawn(async move {
+            if let Some(at) = at.as_instant() {
+                tokio::time::sleep_until(at.into()).await;
+            } else {
+                while !at.is_ready() {
+                    tokio::time::sleep(at.remaining().min(Duration::from_secs(1))).await;
+                }
+            }
+            action.call();
+        });
+        SpawnAtTask::from_cancel(Action::new(move || task.abort()))
+    }
+}
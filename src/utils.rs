@@ -1,8 +1,11 @@
 mod action;
 pub use action::Action;
 
+pub(crate) mod async_mutex;
 pub(crate) mod bipartite_graph;
 pub(crate) mod btree_multi_map;
 pub(crate) mod inf_vec;
 pub(crate) mod shared_queue;
+pub(crate) mod timer_wheel;
+pub(crate) mod trace;
 pub(crate) mod usize_set;
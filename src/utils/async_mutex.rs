@@ -0,0 +1,123 @@
+use std::{
+    mem::{ManuallyDrop, take},
+    ops::{Deref, DerefMut},
+    sync::{
+        LockResult, Mutex, MutexGuard, PoisonError, TryLockError, TryLockResult,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll, Waker},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// A [`Mutex`] that can also be acquired without blocking the thread.
+///
+/// Tasks waiting in [`poll_lock`](Self::poll_lock) are woken when any guard is dropped,
+/// including guards acquired by the blocking [`lock`](Self::lock).
+pub struct AsyncMutex<T> {
+    value: Mutex<T>,
+    waiters: Mutex<Vec<Waker>>,
+    has_waiters: AtomicBool,
+}
+
+impl<T> AsyncMutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Mutex::new(value),
+            waiters: Mutex::new(Vec::new()),
+            has_waiters: AtomicBool::new(false),
+        }
+    }
+    pub fn lock(&self) -> LockResult<AsyncMutexGuard<'_, T>> {
+        self.map_result(self.value.lock())
+    }
+    pub fn try_lock(&self) -> TryLockResult<AsyncMutexGuard<'_, T>> {
+        match self.value.try_lock() {
+            Ok(guard) => Ok(self.guard(guard)),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(
+                self.guard(e.into_inner()),
+            ))),
+        }
+    }
+    pub fn poll_lock(&self, cx: &Context) -> Poll<LockResult<AsyncMutexGuard<'_, T>>> {
+        if let Some(r) = self.poll_try_lock() {
+            return Poll::Ready(r);
+        }
+        let mut waiters = self.waiters.lock().unwrap();
+        // A task polled again before the lock is released is already registered.
+        if !waiters.iter().any(|w| w.will_wake(cx.waker())) {
+            waiters.push(cx.waker().clone());
+        }
+        self.has_waiters.store(true, Ordering::SeqCst);
+        drop(waiters);
+
+        // Try again in case the guard was dropped before the waker was registered.
+        match self.poll_try_lock() {
+            Some(r) => Poll::Ready(r),
+            None => Poll::Pending,
+        }
+    }
+    pub fn clear_poison(&self) {
+        self.value.clear_poison();
+    }
+    fn poll_try_lock(&self) -> Option<LockResult<AsyncMutexGuard<'_, T>>> {
+        match self.try_lock() {
+            Ok(guard) => Some(Ok(guard)),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(e)) => Some(Err(e)),
+        }
+    }
+    fn map_result<'a>(
+        &'a self,
+        r: LockResult<MutexGuard<'a, T>>,
+    ) -> LockResult<AsyncMutexGuard<'a, T>> {
+        match r {
+            Ok(guard) => Ok(self.guard(guard)),
+            Err(e) => Err(PoisonError::new(self.guard(e.into_inner()))),
+        }
+    }
+    fn guard<'a>(&'a self, guard: MutexGuard<'a, T>) -> AsyncMutexGuard<'a, T> {
+        AsyncMutexGuard {
+            guard: ManuallyDrop::new(guard),
+            mutex: self,
+        }
+    }
+    fn wake_waiters(&self) {
+        if !self.has_waiters.load(Ordering::SeqCst) {
+            return;
+        }
+        let waiters = {
+            let mut waiters = self.waiters.lock().unwrap();
+            self.has_waiters.store(false, Ordering::SeqCst);
+            take(&mut *waiters)
+        };
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+pub struct AsyncMutexGuard<'a, T> {
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+    mutex: &'a AsyncMutex<T>,
+}
+impl<T> Deref for AsyncMutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+impl<T> DerefMut for AsyncMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+impl<T> Drop for AsyncMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: `guard` is not used after this.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        self.mutex.wake_waiters();
+    }
+}
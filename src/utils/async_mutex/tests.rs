@@ -0,0 +1,75 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use futures::task::{ArcWake, waker};
+
+use super::*;
+
+struct CountWake(AtomicUsize);
+impl ArcWake for CountWake {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn try_lock_contended() {
+    let m = AsyncMutex::new(0);
+    let guard = m.lock().unwrap();
+    assert!(matches!(m.try_lock(), Err(TryLockError::WouldBlock)));
+    drop(guard);
+    assert!(m.try_lock().is_ok());
+}
+
+#[test]
+fn poll_lock_wakes_on_unlock() {
+    let m = AsyncMutex::new(0);
+    let count = Arc::new(CountWake(AtomicUsize::new(0)));
+    let waker = waker(count.clone());
+    let cx = Context::from_waker(&waker);
+
+    let mut guard = m.lock().unwrap();
+    assert!(m.poll_lock(&cx).is_pending());
+    *guard = 1;
+    drop(guard);
+    assert_eq!(count.0.load(Ordering::SeqCst), 1);
+
+    let Poll::Ready(Ok(guard)) = m.poll_lock(&cx) else {
+        panic!("lock is not acquired");
+    };
+    assert_eq!(*guard, 1);
+    drop(guard);
+    assert_eq!(count.0.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn poll_lock_registers_each_waker_once() {
+    let m = AsyncMutex::new(0);
+    let count = Arc::new(CountWake(AtomicUsize::new(0)));
+    let waker = waker(count.clone());
+    let cx = Context::from_waker(&waker);
+
+    let guard = m.lock().unwrap();
+    for _ in 0..3 {
+        assert!(m.poll_lock(&cx).is_pending());
+    }
+    assert_eq!(m.waiters.lock().unwrap().len(), 1);
+    drop(guard);
+    assert_eq!(count.0.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn poisoned() {
+    let m = AsyncMutex::new(0);
+    let r = std::panic::catch_unwind(|| {
+        let _guard = m.lock().unwrap();
+        panic!("poison");
+    });
+    assert!(r.is_err());
+    assert!(m.lock().is_err());
+    assert!(matches!(m.try_lock(), Err(TryLockError::Poisoned(_))));
+    m.clear_poison();
+    assert!(m.lock().is_ok());
+}
@@ -138,6 +138,16 @@ impl<X, Y, E> BipartiteGraph<X, Y, E> {
         self.ys.get_mut(y.0).map(|node| &mut node.data)
     }
 
+    pub fn x_len(&self) -> usize {
+        self.xs.len()
+    }
+    pub fn y_len(&self) -> usize {
+        self.ys.len()
+    }
+    pub fn edge_len(&self) -> usize {
+        self.es.len()
+    }
+
     pub fn contains_x(&self, x: XKey) -> bool {
         self.xs.contains_key(x.0)
     }
@@ -21,6 +21,9 @@ where
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
 
     pub fn insert(&mut self, key: K, value: V) -> usize {
         let mut e_id = self.ids.entry(key);
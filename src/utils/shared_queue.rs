@@ -18,6 +18,7 @@ pub struct SharedQueue<T> {
     values: VecDeque<T>,
     ref_counts: VecDeque<usize>,
     age_base: usize,
+    retain: usize,
 }
 
 impl<T> SharedQueue<T> {
@@ -26,21 +27,66 @@ impl<T> SharedQueue<T> {
             values: VecDeque::new(),
             ref_counts: vec![0].into(),
             age_base: 0,
+            retain: 0,
         }
     }
+    /// Creates a cursor that reads the values pushed after this call,
+    /// preceded by the values retained by [`set_retain`](Self::set_retain).
     pub fn create_cursor(&mut self) -> SharedQueueCursor<T> {
-        self.increment_ref_count(self.values.len());
+        let index = self.values.len().saturating_sub(self.retain);
+        self.increment_ref_count(index);
         SharedQueueCursor {
-            age: self.end_age(),
+            age: self.index_to_age(index),
             _phantom: PhantomData,
         }
     }
     pub fn drop_cursor(&mut self, cursor: SharedQueueCursor<T>) {
-        let index = self.age_to_index(cursor.age);
+        let (index, _) = self.cursor_index(&cursor);
         self.decrement_ref_count(index);
     }
-    fn end_age(&self) -> usize {
-        self.age_base + self.values.len()
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the number of values that some cursor has not read yet.
+    pub fn unread_len(&self) -> usize {
+        match self.ref_counts.iter().position(|&ref_count| ref_count > 0) {
+            Some(index) => self.values.len() - index,
+            None => 0,
+        }
+    }
+
+    /// Keeps the newest `retain` values even if no cursor needs them, so that new cursors can read them.
+    pub fn set_retain(&mut self, retain: usize) {
+        self.retain = retain;
+        self.drop_unused();
+    }
+
+    /// Removes and returns the next value of `cursor` if no other cursor still needs it.
+    ///
+    /// Values dropped by [`pop_oldest`](Self::pop_oldest) before `cursor` read them are skipped without being reported.
+    pub fn take(&mut self, cursor: &mut SharedQueueCursor<T>) -> Option<T> {
+        let (index, _) = self.cursor_index(cursor);
+        if index != 0 || self.ref_counts[0] != 1 {
+            return None;
+        }
+        let value = self.values.pop_front()?;
+        self.ref_counts.pop_front();
+        self.ref_counts[0] += 1;
+        self.age_base = self.age_base.wrapping_add(1);
+        cursor.age = self.age_base;
+        Some(value)
+    }
+
+    /// Drops the oldest value even if some cursors have not read it.
+    ///
+    /// Those cursors are moved to the next value, and report the values they missed through [`SharedQueueReader::lagged`].
+    pub fn pop_oldest(&mut self) -> Option<T> {
+        let value = self.values.pop_front()?;
+        let ref_count = self.ref_counts.pop_front().unwrap();
+        self.ref_counts[0] += ref_count;
+        self.age_base = self.age_base.wrapping_add(1);
+        Some(value)
     }
     fn index_to_age(&self, index: usize) -> usize {
         self.age_base.wrapping_add(index)
@@ -48,6 +94,16 @@ impl<T> SharedQueue<T> {
     fn age_to_index(&self, age: usize) -> usize {
         age.wrapping_sub(self.age_base)
     }
+
+    /// Returns the index of `cursor` and the number of values it has missed because of [`pop_oldest`](Self::pop_oldest).
+    fn cursor_index(&self, cursor: &SharedQueueCursor<T>) -> (usize, usize) {
+        let index = self.age_to_index(cursor.age);
+        if index <= self.values.len() {
+            (index, 0)
+        } else {
+            (0, self.age_base.wrapping_sub(cursor.age))
+        }
+    }
     fn increment_ref_count(&mut self, index: usize) {
         let ref_count = &mut self.ref_counts[index];
         assert!(*ref_count < usize::MAX, "ref_count is MAX");
@@ -57,9 +113,11 @@ impl<T> SharedQueue<T> {
         let ref_count = &mut self.ref_counts[index];
         assert!(*ref_count > 0, "ref_count is 0");
         *ref_count -= 1;
-
+        self.drop_unused();
+    }
+    fn drop_unused(&mut self) {
         while let Some(&ref_count) = self.ref_counts.front() {
-            if self.values.is_empty() || ref_count > 0 {
+            if self.values.len() <= self.retain || ref_count > 0 {
                 break;
             }
             self.values.pop_front();
@@ -73,21 +131,23 @@ impl<T> SharedQueue<T> {
     }
 
     pub fn push(&mut self, value: T) {
-        if self.values.is_empty() && self.ref_counts[0] == 0 {
+        if self.retain == 0 && self.values.is_empty() && self.ref_counts[0] == 0 {
             return;
         }
         self.values.push_back(value);
         self.ref_counts.push_back(0);
+        self.drop_unused();
     }
 
     pub fn read<'a>(
         &'a mut self,
         cursor: &'a mut SharedQueueCursor<T>,
     ) -> SharedQueueReader<'a, T> {
-        let index = self.age_to_index(cursor.age);
+        let (index, lagged) = self.cursor_index(cursor);
         SharedQueueReader {
             index_old: index,
             index,
+            lagged,
             cursor,
             queue: self,
         }
@@ -107,10 +167,15 @@ impl<T> Extend<T> for SharedQueue<T> {
 pub struct SharedQueueReader<'a, T> {
     index_old: usize,
     index: usize,
+    lagged: usize,
     cursor: &'a mut SharedQueueCursor<T>,
     queue: &'a mut SharedQueue<T>,
 }
 impl<T> SharedQueueReader<'_, T> {
+    /// Returns the number of values dropped by [`SharedQueue::pop_oldest`] before the cursor read them.
+    pub fn lagged(&self) -> usize {
+        self.lagged
+    }
     pub fn pop(&mut self) -> Option<&T> {
         let value = self.queue.values.get(self.index)?;
         self.index += 1;
@@ -119,6 +119,55 @@ fn create_cursor_when_unread_value_exists() {
     drop(reader1);
 }
 
+#[test]
+fn pop_oldest_reports_lagged() {
+    let mut queue = SharedQueue::new();
+    let mut cursor1 = queue.create_cursor();
+    let mut cursor2 = queue.create_cursor();
+    queue.push(1);
+    queue.push(2);
+    queue.push(3);
+
+    let mut reader2 = queue.read(&mut cursor2);
+    assert_eq!(reader2.pop(), Some(&1));
+    drop(reader2);
+
+    assert_eq!(queue.pop_oldest(), Some(1));
+    assert_eq!(queue.pop_oldest(), Some(2));
+    assert_eq!(queue.len(), 1);
+
+    let mut reader1 = queue.read(&mut cursor1);
+    assert_eq!(reader1.lagged(), 2);
+    assert_eq!(reader1.pop(), Some(&3));
+    assert_eq!(reader1.pop(), None);
+    drop(reader1);
+
+    let mut reader2 = queue.read(&mut cursor2);
+    assert_eq!(reader2.lagged(), 1);
+    assert_eq!(reader2.pop(), Some(&3));
+    drop(reader2);
+
+    assert_eq!(queue.len(), 0);
+    let reader1 = queue.read(&mut cursor1);
+    assert_eq!(reader1.lagged(), 0);
+    drop(reader1);
+    queue.drop_cursor(cursor1);
+    queue.drop_cursor(cursor2);
+    assert_eq!(queue.ref_counts.len(), 1);
+}
+
+#[test]
+fn drop_lagged_cursor() {
+    let mut queue = SharedQueue::new();
+    let cursor = queue.create_cursor();
+    queue.push(1);
+    queue.push(2);
+    queue.pop_oldest();
+    queue.drop_cursor(cursor);
+    assert_eq!(queue.values.len(), 0);
+    assert_eq!(queue.ref_counts.len(), 1);
+}
+
 #[test]
 #[should_panic]
 fn ref_count_underflow() {
@@ -126,3 +175,94 @@ fn ref_count_underflow() {
     queue.push(1);
     queue.decrement_ref_count(0);
 }
+
+#[test]
+fn retain_without_cursor() {
+    let mut queue = SharedQueue::new();
+    queue.set_retain(2);
+    queue.push(1);
+    queue.push(2);
+    queue.push(3);
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.unread_len(), 0);
+
+    let mut cursor = queue.create_cursor();
+    assert_eq!(queue.unread_len(), 2);
+    let mut reader = queue.read(&mut cursor);
+    assert_eq!(reader.pop(), Some(&2));
+    assert_eq!(reader.pop(), Some(&3));
+    assert_eq!(reader.pop(), None);
+    drop(reader);
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.unread_len(), 0);
+
+    queue.set_retain(0);
+    assert_eq!(queue.len(), 0);
+    queue.drop_cursor(cursor);
+}
+
+#[test]
+fn retain_keeps_unread_values() {
+    let mut queue = SharedQueue::new();
+    queue.set_retain(1);
+    let mut cursor = queue.create_cursor();
+    queue.push(1);
+    queue.push(2);
+    queue.push(3);
+    assert_eq!(queue.len(), 3);
+
+    let mut reader = queue.read(&mut cursor);
+    assert_eq!(reader.pop(), Some(&1));
+    drop(reader);
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.unread_len(), 2);
+    queue.drop_cursor(cursor);
+    assert_eq!(queue.len(), 1);
+}
+
+#[test]
+fn take_by_single_cursor() {
+    let mut queue = SharedQueue::new();
+    let mut cursor = queue.create_cursor();
+    queue.push(1);
+    queue.push(2);
+
+    assert_eq!(queue.take(&mut cursor), Some(1));
+    queue.push(3);
+    assert_eq!(queue.take(&mut cursor), Some(2));
+    assert_eq!(queue.take(&mut cursor), Some(3));
+    assert_eq!(queue.take(&mut cursor), None);
+    assert_eq!(queue.len(), 0);
+
+    queue.push(4);
+    assert_eq!(queue.read(&mut cursor).pop(), Some(&4));
+    queue.drop_cursor(cursor);
+}
+
+#[test]
+fn take_waits_for_other_cursors() {
+    let mut queue = SharedQueue::new();
+    let mut cursor1 = queue.create_cursor();
+    let mut cursor2 = queue.create_cursor();
+    queue.push(1);
+
+    assert_eq!(queue.take(&mut cursor1), None);
+    assert_eq!(queue.read(&mut cursor2).pop(), Some(&1));
+    assert_eq!(queue.take(&mut cursor1), Some(1));
+
+    queue.drop_cursor(cursor1);
+    queue.drop_cursor(cursor2);
+}
+
+#[test]
+fn take_skips_popped_values() {
+    let mut queue = SharedQueue::new();
+    let mut cursor = queue.create_cursor();
+    queue.push(1);
+    queue.push(2);
+    queue.pop_oldest();
+
+    assert_eq!(queue.take(&mut cursor), Some(2));
+    assert_eq!(queue.take(&mut cursor), None);
+    queue.drop_cursor(cursor);
+}
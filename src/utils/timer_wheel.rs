@@ -0,0 +1,246 @@
+use std::mem;
+
+use slabmap::SlabMap;
+
+#[cfg(test)]
+mod tests;
+
+const LEVEL_BITS: usize = 6;
+const LEVEL_SLOTS: usize = 1 << LEVEL_BITS;
+const LEVELS: usize = 6;
+
+/// The maximum distance between the current tick and a tick that can be placed without wrapping around the top level.
+const MAX_DURATION: u64 = (1 << (LEVEL_BITS * LEVELS)) - 1;
+
+/// Hierarchical hashed timing wheel.
+///
+/// Entries are scheduled at integer ticks. Inserting and removing an entry is O(1),
+/// and entries are cascaded down to lower levels as the wheel advances.
+/// Entries with the same tick are returned in insertion order.
+pub struct TimerWheel<T> {
+    elapsed: u64,
+    levels: [Level; LEVELS],
+    expired: List,
+    entries: SlabMap<Entry<T>>,
+    next_id: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerWheelKey {
+    key: usize,
+    id: u64,
+}
+
+struct Entry<T> {
+    when: u64,
+    id: u64,
+    value: T,
+    pos: Pos,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pos {
+    Expired,
+    Slot { level: usize, slot: usize },
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct List {
+    head: Option<usize>,
+    tail: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    occupied: u64,
+    slots: [List; LEVEL_SLOTS],
+}
+impl Level {
+    const EMPTY: Self = Self {
+        occupied: 0,
+        slots: [List {
+            head: None,
+            tail: None,
+        }; LEVEL_SLOTS],
+    };
+
+    fn next_expiration(&self, level: usize, now: u64) -> Option<(usize, u64)> {
+        if self.occupied == 0 {
+            return None;
+        }
+        let slot_range = slot_range(level);
+        let level_range = slot_range * LEVEL_SLOTS as u64;
+        let now_slot = ((now / slot_range) % LEVEL_SLOTS as u64) as u32;
+        let zeros = self.occupied.rotate_right(now_slot).trailing_zeros() as usize;
+        let slot = (zeros + now_slot as usize) % LEVEL_SLOTS;
+        let level_start = now & !(level_range - 1);
+        let mut deadline = level_start + slot as u64 * slot_range;
+        if deadline <= now {
+            // Only the top level wraps around, because ticks further than `MAX_DURATION` are placed in it.
+            deadline += level_range;
+        }
+        Some((slot, deadline))
+    }
+}
+
+impl<T> TimerWheel<T> {
+    pub fn new() -> Self {
+        Self {
+            elapsed: 0,
+            levels: [Level::EMPTY; LEVELS],
+            expired: List::default(),
+            entries: SlabMap::new(),
+            next_id: 0,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn insert(&mut self, when: u64, value: T) -> TimerWheelKey {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let key = self.entries.insert(Entry {
+            when,
+            id,
+            value,
+            pos: Pos::Expired,
+            prev: None,
+            next: None,
+        });
+        self.link(key);
+        TimerWheelKey { key, id }
+    }
+    pub fn remove(&mut self, key: TimerWheelKey) -> Option<T> {
+        if self.entries.get(key.key)?.id != key.id {
+            return None;
+        }
+        self.unlink(key.key);
+        Some(self.entries.remove(key.key).unwrap().value)
+    }
+
+    /// Moves the current tick to `elapsed`, which may be earlier than the current one, and places all entries again.
+    pub fn reset(&mut self, elapsed: u64) {
+        self.elapsed = elapsed;
+        self.levels = [Level::EMPTY; LEVELS];
+        self.expired = List::default();
+        let mut keys: Vec<_> = self.entries.iter().map(|(key, e)| (e.id, key)).collect();
+        keys.sort_unstable();
+        for (_, key) in keys {
+            self.link(key);
+        }
+    }
+
+    /// Returns the earliest tick at which [`poll`](Self::poll) may make progress.
+    ///
+    /// This is not necessarily the tick of an entry, because entries in upper levels must be cascaded first.
+    pub fn next_expiration(&self) -> Option<u64> {
+        if self.expired.head.is_some() {
+            return Some(self.elapsed);
+        }
+        Some(self.next_slot()?.2)
+    }
+
+    /// Removes and returns an entry whose tick is at or before `now`.
+    pub fn poll(&mut self, now: u64) -> Option<T> {
+        loop {
+            if let Some(key) = self.expired.head {
+                self.unlink(key);
+                return Some(self.entries.remove(key).unwrap().value);
+            }
+            match self.next_slot() {
+                Some((level, slot, deadline)) if deadline <= now => {
+                    self.elapsed = deadline;
+                    let l = &mut self.levels[level];
+                    l.occupied &= !(1 << slot);
+                    let mut next = mem::take(&mut l.slots[slot]).head;
+                    while let Some(key) = next {
+                        next = self.entries[key].next;
+                        self.link(key);
+                    }
+                }
+                _ => {
+                    self.elapsed = self.elapsed.max(now);
+                    return None;
+                }
+            }
+        }
+    }
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        for (level, l) in self.levels.iter().enumerate() {
+            if let Some((slot, deadline)) = l.next_expiration(level, self.elapsed) {
+                return Some((level, slot, deadline));
+            }
+        }
+        None
+    }
+
+    fn link(&mut self, key: usize) {
+        let when = self.entries[key].when;
+        let pos = if when <= self.elapsed {
+            Pos::Expired
+        } else {
+            let level = level_for(self.elapsed, when);
+            Pos::Slot {
+                level,
+                slot: ((when >> (level * LEVEL_BITS)) % LEVEL_SLOTS as u64) as usize,
+            }
+        };
+        let list = match pos {
+            Pos::Expired => &mut self.expired,
+            Pos::Slot { level, slot } => {
+                self.levels[level].occupied |= 1 << slot;
+                &mut self.levels[level].slots[slot]
+            }
+        };
+        let e = &mut self.entries[key];
+        e.pos = pos;
+        e.prev = list.tail;
+        e.next = None;
+        match list.tail {
+            Some(tail) => self.entries[tail].next = Some(key),
+            None => list.head = Some(key),
+        }
+        list.tail = Some(key);
+    }
+    fn unlink(&mut self, key: usize) {
+        let Entry {
+            pos, prev, next, ..
+        } = self.entries[key];
+        let list = match pos {
+            Pos::Expired => &mut self.expired,
+            Pos::Slot { level, slot } => &mut self.levels[level].slots[slot],
+        };
+        match prev {
+            Some(prev) => self.entries[prev].next = next,
+            None => list.head = next,
+        }
+        match next {
+            Some(next) => self.entries[next].prev = prev,
+            None => list.tail = prev,
+        }
+        if let Pos::Slot { level, slot } = pos {
+            if list.head.is_none() {
+                self.levels[level].occupied &= !(1 << slot);
+            }
+        }
+    }
+}
+
+fn slot_range(level: usize) -> u64 {
+    1 << (level * LEVEL_BITS)
+}
+fn level_for(elapsed: u64, when: u64) -> usize {
+    const SLOT_MASK: u64 = (1 << LEVEL_BITS) - 1;
+    let mut masked = (elapsed ^ when) | SLOT_MASK;
+    if masked >= MAX_DURATION {
+        masked = MAX_DURATION - 1;
+    }
+    let significant = 63 - masked.leading_zeros() as usize;
+    significant / LEVEL_BITS
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use proptest::prelude::*;
+use test_strategy::{Arbitrary, proptest};
+
+use super::{MAX_DURATION, TimerWheel};
+
+fn poll_all(wheel: &mut TimerWheel<u32>, now: u64) -> Vec<u32> {
+    let mut values = Vec::new();
+    while let Some(value) = wheel.poll(now) {
+        values.push(value);
+    }
+    values
+}
+
+#[test]
+fn new_is_empty() {
+    let wheel: TimerWheel<u32> = TimerWheel::new();
+    assert!(wheel.is_empty());
+    assert_eq!(wheel.next_expiration(), None);
+}
+
+#[test]
+fn poll_in_order() {
+    let mut wheel = TimerWheel::new();
+    wheel.insert(5000, 3);
+    wheel.insert(10, 1);
+    wheel.insert(100, 2);
+    assert_eq!(wheel.len(), 3);
+
+    assert_eq!(poll_all(&mut wheel, 9), Vec::<u32>::new());
+    assert_eq!(poll_all(&mut wheel, 10), vec![1]);
+    assert_eq!(poll_all(&mut wheel, 4999), vec![2]);
+    assert_eq!(poll_all(&mut wheel, 5000), vec![3]);
+    assert!(wheel.is_empty());
+}
+
+#[test]
+fn same_tick_in_insertion_order() {
+    let mut wheel = TimerWheel::new();
+    for value in 0..10 {
+        wheel.insert(1000, value);
+    }
+    assert_eq!(poll_all(&mut wheel, 1000), (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn insert_past_is_expired() {
+    let mut wheel = TimerWheel::new();
+    assert_eq!(poll_all(&mut wheel, 100), Vec::<u32>::new());
+    wheel.insert(50, 1);
+    assert_eq!(wheel.next_expiration(), Some(100));
+    assert_eq!(poll_all(&mut wheel, 100), vec![1]);
+}
+
+#[test]
+fn remove() {
+    let mut wheel = TimerWheel::new();
+    let key1 = wheel.insert(10, 1);
+    let key2 = wheel.insert(10, 2);
+    assert_eq!(wheel.remove(key1), Some(1));
+    assert_eq!(wheel.remove(key1), None);
+    assert_eq!(poll_all(&mut wheel, 10), vec![2]);
+    assert_eq!(wheel.remove(key2), None);
+}
+
+#[test]
+fn remove_reused_key() {
+    let mut wheel = TimerWheel::new();
+    let key1 = wheel.insert(10, 1);
+    assert_eq!(wheel.remove(key1), Some(1));
+    let key2 = wheel.insert(20, 2);
+    assert_eq!(wheel.remove(key1), None);
+    assert_eq!(wheel.remove(key2), Some(2));
+}
+
+#[test]
+fn beyond_max_duration() {
+    let mut wheel = TimerWheel::new();
+    wheel.insert(MAX_DURATION * 3 + 7, 1);
+    let mut now = 0;
+    loop {
+        let next = wheel.next_expiration().unwrap();
+        assert!(next <= MAX_DURATION * 3 + 7);
+        now = now.max(next);
+        if let Some(value) = wheel.poll(now) {
+            assert_eq!(value, 1);
+            assert_eq!(now, MAX_DURATION * 3 + 7);
+            break;
+        }
+    }
+}
+
+#[test]
+fn reset() {
+    let mut wheel = TimerWheel::new();
+    assert_eq!(poll_all(&mut wheel, 10_000), Vec::<u32>::new());
+    wheel.insert(20_000, 2);
+    wheel.insert(15_000, 1);
+    wheel.reset(0);
+    wheel.insert(100, 0);
+    assert_eq!(poll_all(&mut wheel, 99), Vec::<u32>::new());
+    assert_eq!(poll_all(&mut wheel, 100), vec![0]);
+    assert_eq!(poll_all(&mut wheel, 20_000), vec![1, 2]);
+}
+
+#[derive(Debug, Arbitrary)]
+enum Op {
+    Insert(#[strategy(0..100_000u64)] u64),
+    Remove(#[strategy(0..64usize)] usize),
+    Advance(#[strategy(0..10_000u64)] u64),
+}
+
+#[proptest]
+fn same_as_btree_map(#[strategy(proptest::collection::vec(any::<Op>(), 0..200))] ops: Vec<Op>) {
+    let mut wheel = TimerWheel::new();
+    let mut expected = BTreeMap::new();
+    let mut keys = Vec::new();
+    let mut now = 0;
+    for (value, op) in ops.into_iter().enumerate() {
+        let value = value as u32;
+        match op {
+            Op::Insert(delay) => {
+                let when = now + delay;
+                keys.push((wheel.insert(when, value), when, value));
+                expected.insert((when.max(now), value), value);
+            }
+            Op::Remove(index) => {
+                if !keys.is_empty() {
+                    let (key, when, value) = keys.remove(index % keys.len());
+                    let removed = expected.remove(&(when.max(now), value));
+                    prop_assert_eq!(wheel.remove(key), removed);
+                }
+            }
+            Op::Advance(d) => {
+                now += d;
+                let mut e = Vec::new();
+                while let Some(entry) = expected.first_entry() {
+                    if entry.key().0 > now {
+                        break;
+                    }
+                    e.push(entry.remove());
+                }
+                prop_assert_eq!(poll_all(&mut wheel, now), e);
+            }
+        }
+        prop_assert_eq!(wheel.len(), expected.len());
+    }
+}
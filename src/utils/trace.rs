@@ -0,0 +1,28 @@
+//! Macros that forward to `tracing` when the `tracing` feature is enabled, and expand to nothing otherwise.
+
+/// Emits a `tracing` event at the trace level.
+///
+/// Field values are not evaluated unless the `tracing` feature is enabled.
+macro_rules! trace_event {
+    ($($t:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!($($t)*);
+    }};
+}
+
+/// Enters a `tracing` span at the trace level, which is exited when the returned guard is dropped.
+macro_rules! trace_span {
+    ($($t:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        let span = ::tracing::trace_span!($($t)*).entered();
+        #[cfg(not(feature = "tracing"))]
+        let span = $crate::utils::trace::NoSpan;
+        span
+    }};
+}
+
+pub(crate) use {trace_event, trace_span};
+
+/// The guard returned by [`trace_span`] when the `tracing` feature is disabled.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
@@ -0,0 +1,110 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::Poll,
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+
+use sigwake::{
+    StateContainer, StateContext, Timeout,
+    state::Value,
+    time::{AnyTime, SpawnAtTask, ThreadTimer, TimerBackend},
+    utils::Action,
+};
+
+struct St {
+    value: Value<u32>,
+}
+impl St {
+    fn new() -> StateContainer<Self> {
+        StateContainer::new(|cx| Self {
+            value: Value::new(0, cx),
+        })
+    }
+}
+
+fn wait_10(st: &mut St, cx: &mut StateContext) -> Poll<u32> {
+    let value = *st.value.get(cx);
+    if value >= 10 {
+        Poll::Ready(value)
+    } else {
+        Poll::Pending
+    }
+}
+
+#[test]
+fn wait_blocking() {
+    let st = St::new();
+    let t = spawn({
+        let st = st.clone();
+        move || st.wait_blocking(wait_10)
+    });
+    sleep(Duration::from_millis(50));
+    st.update(|st, cx| st.value.set(5, cx));
+    sleep(Duration::from_millis(50));
+    st.update(|st, cx| st.value.set(12, cx));
+    assert_eq!(t.join().unwrap(), 12);
+}
+
+#[test]
+fn wait_blocking_until_timeout() {
+    let st = St::new();
+    let start = Instant::now();
+    let ret = st.wait_blocking_until(Duration::from_millis(100), wait_10);
+    assert_eq!(ret, Err(Timeout));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[test]
+fn wait_blocking_until_ready() {
+    let st = St::new();
+    let t = spawn({
+        let st = st.clone();
+        move || st.wait_blocking_until(Duration::from_secs(10), wait_10)
+    });
+    sleep(Duration::from_millis(50));
+    st.update(|st, cx| st.value.set(10, cx));
+    assert_eq!(t.join().unwrap(), Ok(10));
+}
+
+#[test]
+fn subscribe_blocking() {
+    let st = St::new();
+    let mut values = st.subscribe_blocking(|st, cx| *st.value.get(cx));
+    assert_eq!(values.next(), Some(0));
+    assert_eq!(values.next_until(Duration::from_millis(50)), Err(Timeout));
+    spawn({
+        let st = st.clone();
+        move || {
+            sleep(Duration::from_millis(50));
+            st.update(|st, cx| st.value.set(1, cx));
+        }
+    });
+    assert_eq!(values.next(), Some(1));
+}
+
+#[test]
+fn deadlines_use_timer_backend() {
+    struct Backend(Arc<AtomicUsize>);
+    impl TimerBackend for Backend {
+        fn spawn_at(&self, action: Action, at: AnyTime) -> SpawnAtTask {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            ThreadTimer.spawn_at(action, at)
+        }
+    }
+    let count = Arc::new(AtomicUsize::new(0));
+    let st = St::new();
+    st.set_timer_backend(Backend(count.clone()));
+
+    let ret = st.wait_blocking_until(Duration::from_millis(50), wait_10);
+    assert_eq!(ret, Err(Timeout));
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+
+    let mut values = st.subscribe_blocking(|st, cx| *st.value.get(cx));
+    assert_eq!(values.next(), Some(0));
+    assert_eq!(values.next_until(Duration::from_millis(50)), Err(Timeout));
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+}
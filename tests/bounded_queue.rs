@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use assert_call::{CallRecorder, call};
+use futures::StreamExt;
+use sigwake::{LocalStateContainer, StateContainer, state::BoundedQueue};
+use tokio::{spawn, task::LocalSet, test, time::sleep};
+
+#[test]
+async fn try_push_returns_item_when_full() {
+    let st = StateContainer::new(|cx| BoundedQueue::new(2, cx));
+    st.update(|st, cx| {
+        assert_eq!(st.try_push(1, cx), Ok(()));
+        assert_eq!(st.try_push(2, cx), Ok(()));
+        assert_eq!(st.try_push(3, cx), Err(3));
+        assert!(st.is_full(cx));
+    });
+    let ret = st.poll_fn(|st, cx| st.pop(cx)).await;
+    assert_eq!(ret, 1);
+    st.update(|st, cx| assert_eq!(st.try_push(3, cx), Ok(())));
+}
+
+#[test]
+async fn push_waits_for_space() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(|cx| BoundedQueue::new(1, cx));
+    st.update(|st, cx| st.try_push(1, cx)).unwrap();
+    spawn({
+        let st = st.clone();
+        async move {
+            st.push_bounded(|st| st, 2).await;
+            call!("pushed");
+        }
+    });
+    sleep(Duration::from_millis(100)).await;
+    cr.verify(());
+
+    let ret = st.poll_fn(|st, cx| st.pop(cx)).await;
+    assert_eq!(ret, 1);
+    sleep(Duration::from_millis(100)).await;
+    cr.verify("pushed");
+
+    let ret = st.poll_fn(|st, cx| st.pop(cx)).await;
+    assert_eq!(ret, 2);
+}
+
+#[test]
+async fn push_keeps_item_while_pending() {
+    let st = StateContainer::new(|cx| BoundedQueue::new(1, cx));
+    st.update(|st, cx| st.try_push(1, cx)).unwrap();
+    let mut item = Some(2);
+    st.update(|st, cx| assert!(st.push(&mut item, cx).is_pending()));
+    assert_eq!(item, Some(2));
+
+    st.update(|st, cx| {
+        assert!(st.pop(cx).is_ready());
+        assert!(st.push(&mut item, cx).is_ready());
+    });
+    assert_eq!(item, None);
+}
+
+#[test]
+async fn local_push_waits_for_space() {
+    LocalSet::new()
+        .run_until(async {
+            let st = LocalStateContainer::new(|cx| BoundedQueue::new(1, cx));
+            st.update(|st, cx| st.try_push(1, cx)).unwrap();
+            let task = tokio::task::spawn_local({
+                let st = st.clone();
+                async move { st.push_bounded(|st| st, 2).await }
+            });
+            sleep(Duration::from_millis(100)).await;
+            assert!(!task.is_finished());
+
+            assert_eq!(st.poll_fn(|st, cx| st.pop(cx)).await, 1);
+            task.await.unwrap();
+            assert_eq!(st.poll_fn(|st, cx| st.pop(cx)).await, 2);
+        })
+        .await;
+}
+
+#[test]
+#[should_panic]
+async fn zero_capacity() {
+    StateContainer::new(|cx| BoundedQueue::<u32>::new(0, cx));
+}
+
+#[test]
+async fn len_is_tracked() {
+    let st = StateContainer::new(|cx| BoundedQueue::new(2, cx));
+    let mut lens = st.subscribe(|st, cx| (st.len(cx), st.is_full(cx)));
+    assert_eq!(lens.next().await, Some((0, false)));
+
+    st.update(|st, cx| st.try_push(1, cx)).unwrap();
+    assert_eq!(lens.next().await, Some((1, false)));
+    st.update(|st, cx| st.try_push(2, cx)).unwrap();
+    assert_eq!(lens.next().await, Some((2, true)));
+    st.poll_fn(|st, cx| st.pop(cx)).await;
+    assert_eq!(lens.next().await, Some((1, false)));
+}
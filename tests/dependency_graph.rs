@@ -0,0 +1,138 @@
+use std::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll},
+};
+
+use futures::task::noop_waker;
+use sigwake::{DependencyGraph, SourceNode, StateContainer, StateKey, TargetNode, state::Memo};
+use tokio::test;
+
+struct St {
+    a: StateKey,
+    b: StateKey,
+}
+
+fn new_container() -> StateContainer<St> {
+    StateContainer::new(|cx| St {
+        a: StateKey::new_named("a", cx),
+        b: StateKey::new(cx),
+    })
+}
+
+#[test]
+async fn sources_and_targets() {
+    let c = new_container();
+    assert_eq!(
+        c.dependency_graph(),
+        DependencyGraph {
+            sources: vec![
+                SourceNode {
+                    id: 0,
+                    name: Some("a".into()),
+                },
+                SourceNode { id: 1, name: None },
+            ],
+            targets: vec![],
+        }
+    );
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut f = pin!(c.poll_fn(|st, cx| {
+        st.a.watch(cx);
+        st.b.watch(cx);
+        Poll::<()>::Pending
+    }));
+    assert!(f.as_mut().poll(&mut cx).is_pending());
+    let g = c.dependency_graph();
+    assert_eq!(g.targets.len(), 1);
+    assert_eq!(g.targets[0].sources, [0, 1]);
+    assert!(!g.targets[0].is_woken);
+
+    c.update(|st, cx| st.b.notify(cx));
+    assert!(c.dependency_graph().targets[0].is_woken);
+}
+
+#[test]
+async fn dropped_key_is_removed() {
+    let c = StateContainer::new(|cx| Some(StateKey::new_named("a", cx)));
+    c.update(|st, _| *st = None);
+    assert_eq!(c.dependency_graph().sources, []);
+}
+
+#[test]
+async fn memo_target() {
+    let c = StateContainer::new(|cx| (StateKey::new_named("a", cx), Memo::<u32>::new(cx)));
+    c.update(|(a, memo), cx| {
+        memo.get(cx, |cx| {
+            a.watch(cx);
+            1
+        });
+    });
+    let g = c.dependency_graph();
+    assert_eq!(g.sources.len(), 2);
+    let memo_source = g.sources[1].id;
+    assert_eq!(
+        g.targets,
+        [TargetNode {
+            id: g.targets[0].id,
+            sources: vec![0],
+            derived: Some(memo_source),
+            is_woken: false,
+        }]
+    );
+}
+
+#[test]
+async fn to_dot() {
+    let g = DependencyGraph {
+        sources: vec![
+            SourceNode {
+                id: 0,
+                name: Some("say \"hi\"".into()),
+            },
+            SourceNode { id: 1, name: None },
+        ],
+        targets: vec![TargetNode {
+            id: 0,
+            sources: vec![0],
+            derived: Some(1),
+            is_woken: false,
+        }],
+    };
+    assert_eq!(
+        g.to_dot(),
+        r##"digraph sigwake {
+  s0 [label="say \"hi\"" shape=ellipse];
+  s1 [label="#1" shape=ellipse];
+  t0 [label="target 0" shape=box];
+  s0 -> t0;
+  t0 -> s1 [style=dashed];
+}
+"##
+    );
+}
+
+#[test]
+async fn to_json() {
+    let g = DependencyGraph {
+        sources: vec![
+            SourceNode {
+                id: 0,
+                name: Some("a\nb".into()),
+            },
+            SourceNode { id: 1, name: None },
+        ],
+        targets: vec![TargetNode {
+            id: 0,
+            sources: vec![0, 1],
+            derived: None,
+            is_woken: true,
+        }],
+    };
+    assert_eq!(
+        g.to_json(),
+        r#"{"sources":[{"id":0,"name":"a\nb"},{"id":1,"name":null}],"targets":[{"id":0,"sources":[0,1],"derived":null,"is_woken":true}]}"#
+    );
+}
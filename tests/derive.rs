@@ -0,0 +1,127 @@
+#![cfg(feature = "derive")]
+
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use assert_call::{CallRecorder, call};
+use futures::StreamExt;
+use sigwake::{
+    LocalStateContainer, State,
+    state::{EventChannel, Queue, Value, Vec},
+};
+use tokio::{spawn, test, time::sleep};
+
+#[derive(State)]
+#[state(debug)]
+struct St {
+    a: Value<u32>,
+    name: Value<String>,
+    items: Vec<u32>,
+    #[state(default)]
+    queue: Queue<u32>,
+}
+
+#[derive(State)]
+struct Generic<T: Clone + 'static> {
+    value: Value<T>,
+    #[state(default)]
+    e: EventChannel<T>,
+}
+
+#[derive(State)]
+struct Local {
+    a: Value<u32>,
+    #[state(plain)]
+    handle: Rc<Cell<u32>>,
+}
+
+#[test]
+async fn new_container_and_accessors() {
+    let mut cr = CallRecorder::new();
+    let st = St::new_container(1, "x".to_string(), vec![1, 2]);
+    assert_eq!(st.a(), 1);
+    assert_eq!(st.name(), "x");
+    assert_eq!(st.lock_untracked().items.as_slice_untracked(), [1, 2]);
+
+    let mut values = st.subscribe(|st, cx| *st.a.get(cx));
+    spawn(async move {
+        while let Some(value) = values.next().await {
+            call!("{value}");
+        }
+    });
+    sleep(Duration::from_millis(100)).await;
+    cr.verify("1");
+
+    st.set_a(2);
+    sleep(Duration::from_millis(100)).await;
+    cr.verify("2");
+    assert_eq!(st.a(), 2);
+}
+
+#[test]
+async fn debug() {
+    let st = St::new_container(1, "x".to_string(), vec![]);
+    let st = st.lock_untracked();
+    let s = format!("{:?}", &*st);
+    assert!(s.starts_with(r#"St { a: 1, name: "x", items: "#), "{s}");
+}
+
+#[test]
+async fn generic() {
+    let st = Generic::new_container(5u8);
+    assert_eq!(st.value(), 5);
+    st.set_value(6);
+    assert_eq!(st.value(), 6);
+    st.update(|st, cx| st.e.send(7, cx));
+}
+
+#[test]
+async fn init_with_other_container() {
+    let handle = Rc::new(Cell::new(3));
+    let st = LocalStateContainer::new(|cx| Local::init(cx, 1, handle.clone()));
+    st.update(|st, cx| st.a.set(2, cx));
+    let st = st.lock_untracked();
+    assert_eq!(*st.a.get_untracked() + st.handle.get(), 5);
+}
+
+#[cfg(feature = "serde")]
+mod serde {
+    use sigwake::{
+        State,
+        state::{EventChannel, Memo, Value},
+    };
+    use tokio::test;
+
+    #[derive(State)]
+    #[state(serde)]
+    struct St {
+        a: Value<u32>,
+        #[state(plain)]
+        b: String,
+        #[state(default)]
+        sum: Memo<u32>,
+        #[state(default)]
+        e: EventChannel<u32>,
+    }
+
+    #[test]
+    async fn snapshot_and_restore() -> anyhow::Result<()> {
+        let st = St::new_container(1, "x".to_string());
+        let json = st.snapshot(serde_json::value::Serializer)?;
+        assert_eq!(
+            json,
+            serde_json::json!({ "a": 1, "b": "x", "sum": null, "e": { "capacity": null, "replay": null, "is_work_queue": false } })
+        );
+
+        let st2 = St::new_container(0, String::new());
+        st2.restore(json)?;
+        assert_eq!(st2.a(), 1);
+        assert_eq!(st2.lock_untracked().b, "x");
+
+        st2.restore(serde_json::json!([2, "y", null, null]))?;
+        assert_eq!(st2.a(), 2);
+
+        assert!(st2.restore(serde_json::json!({ "a": 3 })).is_err());
+        assert_eq!(st2.a(), 2);
+        Ok(())
+    }
+}
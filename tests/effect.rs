@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use assert_call::{CallRecorder, call};
+use sigwake::{StateContainer, state::Value};
+use tokio::{spawn, test, time::sleep};
+
+struct St {
+    a: Value<u32>,
+    b: Value<u32>,
+}
+impl St {
+    fn new() -> StateContainer<Self> {
+        StateContainer::new(|cx| Self {
+            a: Value::new(0, cx),
+            b: Value::new(0, cx),
+        })
+    }
+}
+
+async fn wait_sleep() {
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[test]
+async fn effect() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let handle = st
+        .effect(|st, cx| call!("a = {}", st.a.get(cx)))
+        .spawn(|task| {
+            spawn(task);
+        });
+    wait_sleep().await;
+    cr.verify("a = 0");
+
+    st.update(|st, cx| st.a.set(1, cx));
+    wait_sleep().await;
+    cr.verify("a = 1");
+
+    st.update(|st, cx| st.b.set(1, cx));
+    wait_sleep().await;
+    cr.verify(());
+
+    drop(handle);
+    st.update(|st, cx| st.a.set(2, cx));
+    wait_sleep().await;
+    cr.verify(());
+}
+
+#[test]
+async fn effect_with_cleanup() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let handle = st
+        .effect_with_cleanup(|st, cx| {
+            let a = *st.a.get(cx);
+            call!("run {a}");
+            move || call!("cleanup {a}")
+        })
+        .spawn(|task| {
+            spawn(task);
+        });
+    wait_sleep().await;
+    cr.verify("run 0");
+
+    st.update(|st, cx| st.a.set(1, cx));
+    wait_sleep().await;
+    cr.verify(["cleanup 0", "run 1"]);
+
+    drop(handle);
+    wait_sleep().await;
+    cr.verify("cleanup 1");
+}
+
+#[test]
+async fn effect_cleanup_can_update_state() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let st2 = st.clone();
+    let _handle = st
+        .effect_with_cleanup(move |st, cx| {
+            call!("run {}", st.a.get(cx));
+            let st = st2.clone();
+            move || st.update(|st, cx| st.b.set(1, cx))
+        })
+        .spawn(|task| {
+            spawn(task);
+        });
+    wait_sleep().await;
+    cr.verify("run 0");
+
+    st.update(|st, cx| st.a.set(1, cx));
+    wait_sleep().await;
+    cr.verify("run 1");
+    assert_eq!(*st.lock_untracked().b.get_untracked(), 1);
+}
+
+#[test]
+async fn spawn_thread() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let handle = st
+        .effect_with_cleanup(|st, cx| {
+            let a = *st.a.get(cx);
+            call!("run {a}");
+            move || call!("cleanup {a}")
+        })
+        .spawn_thread();
+    wait_sleep().await;
+    cr.verify("run 0");
+
+    st.update(|st, cx| st.a.set(1, cx));
+    wait_sleep().await;
+    cr.verify(["cleanup 0", "run 1"]);
+
+    drop(handle);
+    wait_sleep().await;
+    cr.verify("cleanup 1");
+}
@@ -1,9 +1,16 @@
-use std::time::Duration;
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Result;
 use assert_call::{Call, CallRecorder, call};
-use futures::StreamExt;
-use sigwake::{StateContainer, state::EventChannel};
+use futures::{FutureExt, StreamExt};
+use sigwake::{
+    StateContainer,
+    state::{EventChannel, Lagged, OverflowPolicy, Replay},
+};
 use tokio::{spawn, test, time::sleep};
 
 #[derive(Clone)]
@@ -137,3 +144,195 @@ async fn subscribe_event_with_test() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn bounded(policy: OverflowPolicy) -> StateContainer<EventChannel<u32>> {
+    StateContainer::new(|cx| EventChannel::bounded(2, policy, cx))
+}
+
+#[test]
+async fn drop_oldest_reports_lagged() {
+    let st = bounded(OverflowPolicy::DropOldest);
+    let mut lossy = st.subscribe_event_lossy(|st| st);
+    let mut es = st.subscribe_event(|st| st);
+    st.update(|st, cx| st.send_all([1, 2, 3, 4, 5], cx));
+
+    assert_eq!(lossy.next().await, Some(Err(Lagged(3))));
+    assert_eq!(lossy.next().await, Some(Ok(4)));
+    assert_eq!(lossy.next().await, Some(Ok(5)));
+    assert_eq!(es.next().await, Some(4));
+    assert_eq!(es.next().await, Some(5));
+
+    st.update(|st, cx| st.send(6, cx));
+    assert_eq!(lossy.next().await, Some(Ok(6)));
+    assert_eq!(es.next().await, Some(6));
+}
+
+#[test]
+async fn disconnect_ends_slow_subscriber() {
+    let st = bounded(OverflowPolicy::Disconnect);
+    let mut slow = st.subscribe_event_lossy(|st| st);
+    let mut fast = st.subscribe_event_lossy(|st| st);
+    st.update(|st, cx| st.send_all([1, 2], cx));
+    assert_eq!(fast.next().await, Some(Ok(1)));
+    assert_eq!(fast.next().await, Some(Ok(2)));
+
+    st.update(|st, cx| st.send(3, cx));
+    assert_eq!(slow.next().await, Some(Err(Lagged(1))));
+    assert_eq!(slow.next().await, None);
+    assert_eq!(fast.next().await, Some(Ok(3)));
+}
+
+#[test]
+async fn block_waits_for_slowest_subscriber() {
+    let st = bounded(OverflowPolicy::Block);
+    let mut es = st.subscribe_event(|st| st);
+    st.update(|st, cx| {
+        assert_eq!(st.try_send(1, cx), Ok(()));
+        assert_eq!(st.try_send(2, cx), Ok(()));
+        assert_eq!(st.try_send(3, cx), Err(3));
+    });
+
+    let send = spawn({
+        let st = st.clone();
+        async move { st.send_event(|st| st, 3).await }
+    });
+    sleep(Duration::from_millis(100)).await;
+    assert!(!send.is_finished());
+
+    assert_eq!(es.next().await, Some(1));
+    send.await.unwrap();
+    assert_eq!(es.next().await, Some(2));
+    assert_eq!(es.next().await, Some(3));
+    assert_eq!(es.next().now_or_never(), None);
+}
+
+#[test]
+async fn block_is_released_when_subscriber_is_dropped() {
+    let st = bounded(OverflowPolicy::Block);
+    let es = st.subscribe_event(|st| st);
+    st.update(|st, cx| st.send_all([1, 2], cx));
+    let send = spawn({
+        let st = st.clone();
+        async move { st.send_event(|st| st, 3).await }
+    });
+    sleep(Duration::from_millis(100)).await;
+    assert!(!send.is_finished());
+
+    drop(es);
+    send.await.unwrap();
+}
+
+#[test]
+async fn replay_last_to_late_subscriber() {
+    let st = StateContainer::new(|cx| {
+        let mut e = EventChannel::new(cx);
+        e.set_replay(Some(Replay::Last(2)));
+        e
+    });
+    st.update(|st, cx| st.send_all([1, 2, 3], cx));
+
+    let mut es = st.subscribe_event(|st| st);
+    st.update(|st, cx| st.send(4, cx));
+    assert_eq!(es.next().await, Some(2));
+    assert_eq!(es.next().await, Some(3));
+    assert_eq!(es.next().await, Some(4));
+
+    let mut es = st.subscribe_event(|st| st);
+    assert_eq!(es.next().await, Some(3));
+    assert_eq!(es.next().await, Some(4));
+    assert_eq!(es.next().now_or_never(), None);
+
+    st.update(|st, _| st.set_replay(None));
+    let mut es = st.subscribe_event(|st| st);
+    assert_eq!(es.next().now_or_never(), None);
+}
+
+#[test]
+async fn replay_counts_toward_capacity() {
+    let st = StateContainer::new(|cx| {
+        let mut e = EventChannel::bounded(2, OverflowPolicy::DropOldest, cx);
+        e.set_replay(Some(Replay::Last(5)));
+        e
+    });
+    st.update(|st, cx| st.send_all([1, 2, 3], cx));
+    let mut es = st.subscribe_event(|st| st);
+    assert_eq!(es.next().await, Some(2));
+    assert_eq!(es.next().await, Some(3));
+}
+
+/// A payload that is neither `Clone` nor `Copy`.
+#[derive(Debug, PartialEq)]
+struct Payload(u32);
+
+#[test]
+async fn shared_events_are_not_cloned() {
+    let st = StateContainer::new(EventChannel::<Arc<Payload>>::new);
+    let mut es1 = st.subscribe_event(|st| st);
+    let mut es2 = st.subscribe_event(|st| st);
+    st.update(|st, cx| st.send_shared(Payload(1), cx));
+
+    let e1 = es1.next().await.unwrap();
+    let e2 = es2.next().await.unwrap();
+    assert_eq!(*e1, Payload(1));
+    assert!(Arc::ptr_eq(&e1, &e2));
+}
+
+#[test]
+async fn work_queue_delivers_each_event_once() {
+    let st = StateContainer::new(EventChannel::work_queue);
+    st.update(|st, cx| st.send_all([Payload(1), Payload(2)], cx));
+
+    let mut es1 = st.subscribe_event_owned(|st| st);
+    let mut es2 = st.subscribe_event_owned(|st| st);
+    assert_eq!(es1.next().await, Some(Payload(1)));
+    assert_eq!(es2.next().await, Some(Payload(2)));
+    assert_eq!(es1.next().now_or_never(), None);
+    assert_eq!(es2.next().now_or_never(), None);
+
+    st.update(|st, cx| st.send(Payload(3), cx));
+    drop(es1);
+    assert_eq!(es2.next().await, Some(Payload(3)));
+}
+
+#[test]
+async fn bounded_work_queue_blocks_sender() {
+    let mut cr = CallRecorder::new();
+    let st =
+        StateContainer::new(|cx| EventChannel::bounded_work_queue(1, OverflowPolicy::Block, cx));
+    st.update(|st, cx| st.send(1, cx));
+    spawn({
+        let st = st.clone();
+        async move {
+            st.send_event(|st| st, 2).await;
+            call!("sent");
+        }
+    });
+    wait_sleep().await;
+    cr.verify(());
+
+    let mut es = st.subscribe_event(|st| st);
+    assert_eq!(es.next().await, Some(1));
+    wait_sleep().await;
+    cr.verify("sent");
+    assert_eq!(es.next().await, Some(2));
+}
+
+#[test]
+async fn subscribe_event_owned_requires_work_queue() {
+    let st = StateContainer::new(EventChannel::<u32>::new);
+    let r = catch_unwind(AssertUnwindSafe(|| {
+        let _ = st.subscribe_event_owned(|st| st);
+    }));
+    assert!(r.is_err());
+    // The container is not poisoned by the misuse.
+    st.update(|st, cx| st.send(1, cx));
+}
+
+#[test]
+async fn owned_subscriber_ends_when_replaced_by_broadcast_channel() {
+    let st = StateContainer::new(EventChannel::<u32>::work_queue);
+    let mut es = st.subscribe_event_owned(|st| st);
+    st.update(|st, cx| *st = EventChannel::new(cx));
+    assert_eq!(es.next().await, None);
+    st.update(|st, cx| st.send(1, cx));
+}
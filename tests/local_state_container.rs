@@ -0,0 +1,119 @@
+use std::{cell::Cell, rc::Rc, task::Poll, time::Duration};
+
+use assert_call::{CallRecorder, call};
+use futures::StreamExt;
+use sigwake::{
+    LocalStateContainer, Timeout,
+    state::{EventChannel, Value},
+};
+use tokio::{
+    task::{LocalSet, spawn_local},
+    test,
+    time::sleep,
+};
+
+struct St {
+    a: Value<u32>,
+    e: EventChannel<u32>,
+    handle: Rc<Cell<u32>>,
+}
+impl St {
+    fn new() -> LocalStateContainer<Self> {
+        LocalStateContainer::new(|cx| Self {
+            a: Value::new(0, cx),
+            e: EventChannel::new(cx),
+            handle: Rc::new(Cell::new(0)),
+        })
+    }
+}
+
+async fn wait_sleep() {
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[test]
+async fn subscribe() {
+    LocalSet::new()
+        .run_until(async {
+            let mut cr = CallRecorder::new();
+            let st = St::new();
+            let mut s = st.subscribe(|st, cx| *st.a.get(cx) + st.handle.get());
+            spawn_local(async move {
+                while let Some(value) = s.next().await {
+                    call!("{value}");
+                }
+            });
+            wait_sleep().await;
+            cr.verify("0");
+
+            st.update(|st, cx| {
+                st.handle.set(10);
+                st.a.set(1, cx);
+            });
+            wait_sleep().await;
+            cr.verify("11");
+        })
+        .await;
+}
+
+#[test]
+async fn poll_fn() {
+    LocalSet::new()
+        .run_until(async {
+            let mut cr = CallRecorder::new();
+            let st = St::new();
+            let st2 = st.clone();
+            spawn_local(async move {
+                let a = st2
+                    .poll_fn(|st, cx| {
+                        let a = *st.a.get(cx);
+                        if a >= 2 {
+                            Poll::Ready(a)
+                        } else {
+                            Poll::Pending
+                        }
+                    })
+                    .await;
+                call!("ready {a}");
+            });
+            st.update(|st, cx| st.a.set(1, cx));
+            wait_sleep().await;
+            cr.verify(());
+
+            st.update(|st, cx| st.a.set(2, cx));
+            wait_sleep().await;
+            cr.verify("ready 2");
+        })
+        .await;
+}
+
+#[test]
+async fn poll_fn_until_timeout() {
+    let st = St::new();
+    let r = st
+        .poll_fn_until(Duration::from_millis(50), |_, _| Poll::<()>::Pending)
+        .await;
+    assert_eq!(r, Err(Timeout));
+}
+
+#[test]
+async fn subscribe_event() {
+    LocalSet::new()
+        .run_until(async {
+            let mut cr = CallRecorder::new();
+            let st = St::new();
+            let mut es = st.subscribe_event(|st| &mut st.e);
+            spawn_local(async move {
+                while let Some(e) = es.next().await {
+                    call!("{e}");
+                }
+            });
+            st.update(|st, cx| {
+                st.e.send(1, cx);
+                st.e.send(2, cx);
+            });
+            wait_sleep().await;
+            cr.verify(["1", "2"]);
+        })
+        .await;
+}
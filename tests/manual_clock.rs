@@ -0,0 +1,205 @@
+use std::{
+    sync::LazyLock,
+    task::Poll,
+    time::{Duration, SystemTime},
+};
+
+use assert_call::{CallRecorder, call};
+use futures::{FutureExt, StreamExt, lock::Mutex};
+use sigwake::{
+    StateContainer,
+    state::{EventChannel, Replay},
+    time::{self, ManualClock, MissedTickBehavior, spawn_at},
+    utils::Action,
+};
+use tokio::{spawn, task::yield_now, test};
+
+// The clock is process-wide, so the tests in this file must not run concurrently.
+static LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
+
+async fn run_pending_tasks() {
+    for _ in 0..10 {
+        yield_now().await;
+    }
+}
+
+#[test]
+async fn advance_calls_due_actions_in_order() {
+    let _lock = LOCK.lock().await;
+    let clock = ManualClock::install();
+    let mut cr = CallRecorder::new();
+    let start = time::now();
+    let _task2 = spawn_at(Action::new(|| call!("2")), Duration::from_secs(20));
+    let _task1 = spawn_at(Action::new(|| call!("1")), Duration::from_secs(10));
+
+    clock.advance(Duration::from_secs(5));
+    cr.verify(());
+    assert_eq!(time::now() - start, Duration::from_secs(5));
+
+    clock.advance(Duration::from_secs(60));
+    cr.verify(["1", "2"]);
+    assert_eq!(time::now() - start, Duration::from_secs(65));
+}
+
+#[test]
+async fn set_system_time() {
+    let _lock = LOCK.lock().await;
+    let clock = ManualClock::install();
+    let mut cr = CallRecorder::new();
+    let at = SystemTime::UNIX_EPOCH + Duration::from_secs(4_000_000_000);
+    let _task = spawn_at(Action::new(|| call!("called")), at);
+
+    clock.set_system_time(at - Duration::from_secs(1));
+    cr.verify(());
+    clock.set_system_time(at);
+    cr.verify("called");
+    assert_eq!(time::system_now(), at);
+}
+
+#[test]
+async fn sleep() {
+    let _lock = LOCK.lock().await;
+    let clock = ManualClock::install();
+    let mut cr = CallRecorder::new();
+    spawn(async {
+        time::sleep(Duration::from_secs(3600)).await;
+        call!("woke up");
+    });
+    run_pending_tasks().await;
+    cr.verify(());
+
+    clock.advance(Duration::from_secs(3600));
+    run_pending_tasks().await;
+    cr.verify("woke up");
+}
+
+#[test]
+async fn notify_at() {
+    let _lock = LOCK.lock().await;
+    let clock = ManualClock::install();
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(|_| ());
+    let end = time::now() + Duration::from_secs(60);
+    spawn(async move {
+        st.poll_fn(|_st, cx| {
+            cx.notify_at(end);
+            if time::now() >= end {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        call!("ready");
+    });
+    run_pending_tasks().await;
+    clock.advance(Duration::from_secs(59));
+    run_pending_tasks().await;
+    cr.verify(());
+
+    clock.advance(Duration::from_secs(1));
+    run_pending_tasks().await;
+    cr.verify("ready");
+}
+
+fn interval_ticks(behavior: MissedTickBehavior) -> Vec<u64> {
+    let clock = ManualClock::install();
+    let start = time::now();
+    let mut interval = time::interval(Duration::from_secs(10));
+    interval.set_missed_tick_behavior(behavior);
+    let mut ticks = Vec::new();
+    let mut tick = || {
+        let tick = interval.next().now_or_never()?;
+        Some((tick.unwrap() - start).as_secs())
+    };
+    ticks.extend(tick());
+    clock.advance(Duration::from_secs(35));
+    while let Some(t) = tick() {
+        ticks.push(t);
+    }
+    clock.advance(Duration::from_secs(10));
+    ticks.extend(tick());
+    ticks
+}
+
+#[test]
+async fn interval_missed_tick_behavior() {
+    let _lock = LOCK.lock().await;
+    assert_eq!(
+        interval_ticks(MissedTickBehavior::Burst),
+        [0, 10, 20, 30, 40]
+    );
+    assert_eq!(interval_ticks(MissedTickBehavior::Delay), [0, 10, 45]);
+    assert_eq!(interval_ticks(MissedTickBehavior::Skip), [0, 10, 40]);
+}
+
+#[test]
+async fn spawn_every() {
+    let _lock = LOCK.lock().await;
+    let clock = ManualClock::install();
+    let mut cr = CallRecorder::new();
+    let start = time::now();
+    let task = time::spawn_every(
+        move || call!("{}", (time::now() - start).as_secs()),
+        Duration::from_secs(10),
+        MissedTickBehavior::Burst,
+    );
+    clock.advance(Duration::from_secs(25));
+    cr.verify(["10", "20"]);
+    drop(task);
+    clock.advance(Duration::from_secs(25));
+    cr.verify(());
+}
+
+async fn verify_notify_every(behavior: MissedTickBehavior, expected: &[&str]) {
+    let clock = ManualClock::install();
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(|_| ());
+    let start = time::now();
+    let mut s = st.subscribe(move |_st, cx| {
+        cx.notify_every(Duration::from_secs(10), behavior);
+        (time::now() - start).as_secs()
+    });
+    let task = spawn(async move {
+        while let Some(value) = s.next().await {
+            call!("{value}");
+        }
+    });
+    for secs in [0, 35, 5, 5] {
+        clock.advance(Duration::from_secs(secs));
+        // Ticks that are already due are only called when the clock is advanced.
+        for _ in 0..5 {
+            run_pending_tasks().await;
+            clock.advance(Duration::ZERO);
+        }
+    }
+    task.abort();
+    cr.verify(expected);
+}
+
+#[test]
+async fn notify_every_missed_tick_behavior() {
+    let _lock = LOCK.lock().await;
+    verify_notify_every(MissedTickBehavior::Burst, &["0", "35", "35", "35", "40"]).await;
+    verify_notify_every(MissedTickBehavior::Delay, &["0", "35", "45"]).await;
+    verify_notify_every(MissedTickBehavior::Skip, &["0", "35", "40"]).await;
+}
+
+#[test]
+async fn replay_within() {
+    let _lock = LOCK.lock().await;
+    let clock = ManualClock::install();
+    let st = StateContainer::new(|cx| {
+        let mut e = EventChannel::new(cx);
+        e.set_replay(Some(Replay::Within(Duration::from_secs(10))));
+        e
+    });
+    st.update(|st, cx| st.send(1, cx));
+    clock.advance(Duration::from_secs(5));
+    st.update(|st, cx| st.send(2, cx));
+    clock.advance(Duration::from_secs(6));
+
+    let mut es = st.subscribe_event(|st| st);
+    assert_eq!(es.next().await, Some(2));
+    assert_eq!(es.next().now_or_never(), None);
+}
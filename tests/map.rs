@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use assert_call::{CallRecorder, call};
+use futures::StreamExt;
+use sigwake::{
+    StateContainer,
+    state::{BTreeMap, HashMap},
+};
+use tokio::{spawn, test, time::sleep};
+
+async fn wait_sleep() {
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[test]
+async fn hash_map_get_watches_only_its_key() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(HashMap::<u32, u32>::new);
+    st.update(|st, cx| {
+        st.insert(1, 10, cx);
+        st.insert(2, 20, cx);
+    });
+    let mut s = st.subscribe(|st, cx| st.get(&1, cx).copied());
+    spawn(async move {
+        while let Some(value) = s.next().await {
+            call!("{value:?}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("Some(10)");
+
+    st.update(|st, cx| *st.get_mut(&2, cx).unwrap() = 21);
+    st.update(|st, cx| st.insert(3, 30, cx));
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| st.insert(1, 11, cx));
+    wait_sleep().await;
+    cr.verify("Some(11)");
+
+    st.update(|st, cx| st.remove(&1, cx));
+    wait_sleep().await;
+    cr.verify("None");
+
+    st.update(|st, cx| st.insert(1, 12, cx));
+    wait_sleep().await;
+    cr.verify("Some(12)");
+}
+
+#[test]
+async fn hash_map_len_watches_keys() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(HashMap::<u32, u32>::new);
+    st.update(|st, cx| st.insert(1, 10, cx));
+    let mut s = st.subscribe(|st, cx| st.len(cx));
+    spawn(async move {
+        while let Some(len) = s.next().await {
+            call!("{len}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("1");
+
+    st.update(|st, cx| st.insert(1, 11, cx));
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| st.insert(2, 20, cx));
+    wait_sleep().await;
+    cr.verify("2");
+}
+
+#[test]
+async fn btree_map_iter_watches_all() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(BTreeMap::<u32, u32>::new);
+    st.update(|st, cx| {
+        st.insert(2, 20, cx);
+        st.insert(1, 10, cx);
+    });
+    let mut s = st.subscribe(|st, cx| st.iter(cx).map(|(_, v)| *v).collect::<Vec<_>>());
+    spawn(async move {
+        while let Some(values) = s.next().await {
+            call!("{values:?}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("[10, 20]");
+
+    st.update(|st, cx| *st.get_mut(&2, cx).unwrap() = 21);
+    wait_sleep().await;
+    cr.verify("[10, 21]");
+
+    st.update(|st, cx| st.clear(cx));
+    wait_sleep().await;
+    cr.verify("[]");
+}
+
+#[test]
+async fn btree_map_get_mut_without_modification() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(BTreeMap::<u32, u32>::new);
+    st.update(|st, cx| st.insert(1, 10, cx));
+    let mut s = st.subscribe(|st, cx| st.iter(cx).map(|(_, v)| *v).sum::<u32>());
+    spawn(async move {
+        while let Some(sum) = s.next().await {
+            call!("sum {sum}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("sum 10");
+
+    assert_eq!(st.update(|st, cx| *st.get_mut(&1, cx).unwrap()), 10);
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| *st.get_mut(&1, cx).unwrap() += 1);
+    wait_sleep().await;
+    cr.verify("sum 11");
+}
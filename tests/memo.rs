@@ -0,0 +1,129 @@
+use std::{task::Poll, time::Duration};
+
+use assert_call::{CallRecorder, call};
+use futures::StreamExt;
+use sigwake::{
+    StateContainer, StateContext,
+    state::{Memo, Value},
+};
+use tokio::{spawn, test, time::sleep};
+
+struct St {
+    a: Value<u32>,
+    b: Value<u32>,
+    sum: Memo<u32>,
+    is_even: Memo<bool>,
+}
+impl St {
+    fn new() -> StateContainer<Self> {
+        StateContainer::new(|cx| Self {
+            a: Value::new(0, cx),
+            b: Value::new(0, cx),
+            sum: Memo::new(cx),
+            is_even: Memo::new(cx),
+        })
+    }
+    fn sum(&mut self, cx: &mut StateContext) -> u32 {
+        *self.sum.get(cx, |cx| {
+            call!("compute sum");
+            self.a.get(cx) + self.b.get(cx)
+        })
+    }
+}
+
+async fn wait_sleep() {
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[test]
+async fn recompute_only_when_source_changed() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    assert_eq!(st.update(|st, cx| st.sum(cx)), 0);
+    cr.verify("compute sum");
+
+    assert_eq!(st.update(|st, cx| st.sum(cx)), 0);
+    cr.verify(());
+
+    st.update(|st, cx| st.a.set(2, cx));
+    assert_eq!(st.update(|st, cx| st.sum(cx)), 2);
+    cr.verify("compute sum");
+}
+
+#[test]
+async fn subscribe_memo() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let mut s = st.subscribe(|st, cx| st.sum(cx));
+    spawn(async move {
+        while let Some(sum) = s.next().await {
+            call!("sum {sum}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify(["compute sum", "sum 0"]);
+
+    st.update(|st, cx| st.a.set(3, cx));
+    wait_sleep().await;
+    cr.verify(["compute sum", "sum 3"]);
+
+    st.update(|st, cx| {
+        st.a.set(1, cx);
+        st.b.set(2, cx);
+    });
+    wait_sleep().await;
+    cr.verify("compute sum");
+}
+
+#[test]
+async fn chained_memo() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let mut s = st.subscribe(|st, cx| {
+        let sum = &mut st.sum;
+        let a = &st.a;
+        let b = &st.b;
+        *st.is_even
+            .get(cx, |cx| sum.get(cx, |cx| a.get(cx) + b.get(cx)) % 2 == 0)
+    });
+    spawn(async move {
+        while let Some(is_even) = s.next().await {
+            call!("{is_even}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("true");
+
+    st.update(|st, cx| st.a.set(2, cx));
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| st.b.set(1, cx));
+    wait_sleep().await;
+    cr.verify("false");
+}
+
+#[test]
+async fn poll_fn_memo() {
+    let st = St::new();
+    spawn({
+        let st = st.clone();
+        async move {
+            for i in 1..=5 {
+                wait_sleep().await;
+                st.update(|st, cx| st.a.set(i, cx));
+            }
+        }
+    });
+    let sum = st
+        .poll_fn(|st, cx| {
+            let sum = *st.sum.get(cx, |cx| st.a.get(cx) + st.b.get(cx));
+            if sum >= 3 {
+                Poll::Ready(sum)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    assert_eq!(sum, 3);
+}
@@ -0,0 +1,172 @@
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use assert_call::{CallRecorder, call};
+use futures::StreamExt;
+use sigwake::{
+    ContainerPoisoned, PoisonPolicy, RwStateContainer, StateKey, TryUpdateError, state::Value,
+};
+use tokio::{spawn, test, time::sleep};
+
+struct St {
+    a: Value<u32>,
+    b: Value<u32>,
+}
+impl St {
+    fn new() -> RwStateContainer<Self> {
+        RwStateContainer::new(|cx| Self {
+            a: Value::new(0, cx),
+            b: Value::new(0, cx),
+        })
+    }
+}
+
+async fn wait_sleep() {
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[test]
+async fn subscribe() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let mut s = st.subscribe(|st, cx| *st.a.get(cx));
+    spawn(async move {
+        while let Some(value) = s.next().await {
+            call!("{value}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("0");
+
+    st.update(|st, cx| st.a.set(1, cx));
+    wait_sleep().await;
+    cr.verify("1");
+
+    st.update(|st, cx| st.b.set(1, cx));
+    wait_sleep().await;
+    cr.verify(());
+}
+
+#[test]
+async fn poll_fn() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let st2 = st.clone();
+    spawn(async move {
+        let a = st2
+            .poll_fn(|st, cx| {
+                let a = *st.a.get(cx);
+                if a >= 2 {
+                    Poll::Ready(a)
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+        call!("ready {a}");
+    });
+    st.update(|st, cx| st.a.set(1, cx));
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| st.a.set(2, cx));
+    wait_sleep().await;
+    cr.verify("ready 2");
+}
+
+#[test(flavor = "multi_thread", worker_threads = 2)]
+async fn concurrent_reads() {
+    let st = St::new();
+    let readers = Arc::new(AtomicUsize::new(0));
+    let mut handles = Vec::new();
+    for _ in 0..2 {
+        let st = st.clone();
+        let readers = readers.clone();
+        handles.push(spawn(async move {
+            st.poll_fn(|st, cx| {
+                st.a.get(cx);
+                readers.fetch_add(1, Ordering::SeqCst);
+                let deadline = Instant::now() + Duration::from_secs(5);
+                while readers.load(Ordering::SeqCst) < 2 {
+                    assert!(Instant::now() < deadline, "reads did not overlap");
+                    std::hint::spin_loop();
+                }
+                Poll::Ready(())
+            })
+            .await
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+}
+
+#[test]
+#[should_panic]
+async fn create_key_while_reading() {
+    let st = St::new();
+    st.poll_fn(|_, cx| {
+        StateKey::new(cx);
+        Poll::Ready(())
+    })
+    .await;
+}
+
+fn poison(st: &RwStateContainer<St>) {
+    let r = catch_unwind(AssertUnwindSafe(|| {
+        st.update(|st, cx| {
+            st.a.set(5, cx);
+            panic!("poison");
+        })
+    }));
+    assert!(r.is_err());
+}
+
+#[test]
+async fn poisoned() {
+    let st = St::new();
+    poison(&st);
+    assert_eq!(
+        st.try_update(|st, cx| st.a.set(1, cx)),
+        Err(TryUpdateError::Poisoned(ContainerPoisoned))
+    );
+    assert_eq!(
+        st.try_poll_fn(|_, _| Poll::Ready(())).await,
+        Err(ContainerPoisoned)
+    );
+    assert!(catch_unwind(AssertUnwindSafe(|| st.update(|st, cx| st.a.set(1, cx)))).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| *st.lock_untracked().a.get_untracked())).is_err());
+}
+
+#[test]
+async fn poisoned_recover() {
+    let st = St::new();
+    let task = spawn({
+        let st = st.clone();
+        async move {
+            st.poll_fn(|st, cx| {
+                let a = *st.a.get(cx);
+                let b = *st.b.get(cx);
+                if a + b >= 10 {
+                    Poll::Ready(a + b)
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await
+        }
+    });
+    wait_sleep().await;
+    poison(&st);
+    st.set_poison_policy(PoisonPolicy::Recover);
+    assert_eq!(*st.lock_untracked().a.get_untracked(), 5);
+    st.update(|st, cx| st.b.set(5, cx));
+    assert_eq!(task.await.unwrap(), 10);
+}
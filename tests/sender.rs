@@ -0,0 +1,64 @@
+use std::{thread, time::Duration};
+
+use assert_call::{CallRecorder, call};
+use futures::{SinkExt, StreamExt, stream};
+use sigwake::{
+    StateContainer,
+    state::{EventChannel, EventSender, OverflowPolicy, Queue, QueueSender},
+};
+use tokio::{spawn, test, time::sleep};
+
+#[test]
+async fn event_sender_send_from_thread() {
+    let st = StateContainer::new(EventChannel::new);
+    let mut es = st.subscribe_event(|st| st);
+    let sender = st.event_sender(|st| st);
+    thread::spawn(move || {
+        sender.send(1);
+        sender.clone().send(2);
+    })
+    .join()
+    .unwrap();
+    assert_eq!(es.next().await, Some(1));
+    assert_eq!(es.next().await, Some(2));
+}
+
+#[test]
+async fn forward_stream_to_queue() {
+    let st = StateContainer::new(Queue::new);
+    stream::iter([1, 2, 3])
+        .map(Ok)
+        .forward(st.queue_sender(|st| st))
+        .await
+        .unwrap();
+    for expected in [1, 2, 3] {
+        assert_eq!(st.poll_fn(|st, cx| st.pop(cx)).await, expected);
+    }
+}
+
+#[test]
+async fn sink_waits_for_blocked_channel() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(|cx| EventChannel::bounded(1, OverflowPolicy::Block, cx));
+    let mut es = st.subscribe_event(|st| st);
+    let mut sender = st.event_sender(|st| st);
+    spawn(async move {
+        SinkExt::send(&mut sender, 1).await.unwrap();
+        SinkExt::send(&mut sender, 2).await.unwrap();
+        call!("sent");
+    });
+    sleep(Duration::from_millis(100)).await;
+    cr.verify(());
+
+    assert_eq!(es.next().await, Some(1));
+    sleep(Duration::from_millis(100)).await;
+    cr.verify("sent");
+    assert_eq!(es.next().await, Some(2));
+}
+
+#[test]
+async fn senders_are_send() {
+    fn assert_send<T: Send + Clone>() {}
+    assert_send::<EventSender<u32>>();
+    assert_send::<QueueSender<u32>>();
+}
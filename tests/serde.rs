@@ -0,0 +1,218 @@
+#![cfg(feature = "serde")]
+
+use std::time::Duration;
+
+use assert_call::{CallRecorder, call};
+use futures::StreamExt;
+use serde::{
+    Deserializer, Serialize,
+    de::{MapAccess, Visitor},
+};
+use sigwake::{
+    DeserializeState, StateContainer, StateContext, StateSeed,
+    state::{BoundedQueue, EventChannel, OverflowPolicy, Queue, Replay, Value, Vec},
+};
+use tokio::{spawn, test, time::sleep};
+
+#[derive(Serialize)]
+struct St {
+    a: Value<u32>,
+    items: Vec<String>,
+    queue: Queue<u32>,
+    e: EventChannel<u32>,
+}
+impl St {
+    fn new() -> StateContainer<Self> {
+        StateContainer::new(|cx| Self {
+            a: Value::new(0, cx),
+            items: Vec::new(cx),
+            queue: Queue::new(cx),
+            e: EventChannel::new(cx),
+        })
+    }
+}
+
+impl<'de> DeserializeState<'de> for St {
+    fn deserialize_state<D: Deserializer<'de>>(
+        deserializer: D,
+        cx: &mut StateContext,
+    ) -> Result<Self, D::Error> {
+        struct StVisitor<'a>(&'a mut StateContext);
+        impl<'de> Visitor<'de> for StVisitor<'_> {
+            type Value = St;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "struct St")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<St, A::Error> {
+                let (mut a, mut items, mut queue, mut e) = (None, None, None, None);
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "a" => a = Some(map.next_value_seed(StateSeed::new(self.0))?),
+                        "items" => items = Some(map.next_value_seed(StateSeed::new(self.0))?),
+                        "queue" => queue = Some(map.next_value_seed(StateSeed::new(self.0))?),
+                        "e" => e = Some(map.next_value_seed(StateSeed::new(self.0))?),
+                        _ => return Err(serde::de::Error::unknown_field(&key, FIELDS)),
+                    }
+                }
+                Ok(St {
+                    a: a.ok_or_else(|| serde::de::Error::missing_field("a"))?,
+                    items: items.ok_or_else(|| serde::de::Error::missing_field("items"))?,
+                    queue: queue.ok_or_else(|| serde::de::Error::missing_field("queue"))?,
+                    e: e.ok_or_else(|| serde::de::Error::missing_field("e"))?,
+                })
+            }
+        }
+        const FIELDS: &[&str] = &["a", "items", "queue", "e"];
+        deserializer.deserialize_struct("St", FIELDS, StVisitor(cx))
+    }
+}
+
+async fn wait_sleep() {
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[test]
+async fn snapshot_and_restore() -> anyhow::Result<()> {
+    let st = St::new();
+    st.update(|st, cx| {
+        st.a.set(10, cx);
+        st.items.push("x".to_string(), cx);
+        st.queue.push(1, cx);
+        st.e.send(5, cx);
+    });
+    let json = st.snapshot(serde_json::value::Serializer)?;
+    assert_eq!(
+        json,
+        serde_json::json!({ "a": 10, "items": ["x"], "queue": [1], "e": { "capacity": null, "replay": null, "is_work_queue": false } })
+    );
+
+    let st2 = St::new();
+    st2.restore(json)?;
+    let st2 = st2.lock_untracked();
+    assert_eq!(*st2.a.get_untracked(), 10);
+    assert_eq!(st2.items.as_slice_untracked(), ["x".to_string()]);
+    Ok(())
+}
+
+#[test]
+async fn restore_notifies_dependents() -> anyhow::Result<()> {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let mut values = st.subscribe(|st, cx| *st.a.get(cx));
+    spawn(async move {
+        while let Some(value) = values.next().await {
+            call!("a = {value}");
+        }
+    });
+    let mut events = st.subscribe_event(|st| &mut st.e);
+    spawn(async move {
+        while let Some(e) = events.next().await {
+            call!("e = {e}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("a = 0");
+
+    st.restore(serde_json::json!({ "a": 3, "items": [], "queue": [], "e": null }))?;
+    wait_sleep().await;
+    cr.verify("a = 3");
+
+    st.update(|st, cx| st.e.send(1, cx));
+    wait_sleep().await;
+    cr.verify("e = 1");
+    Ok(())
+}
+
+#[test]
+async fn restore_error_keeps_state() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let mut values = st.subscribe(|st, cx| *st.a.get(cx));
+    spawn(async move {
+        while let Some(value) = values.next().await {
+            call!("a = {value}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("a = 0");
+
+    assert!(st.restore(serde_json::json!({ "a": "x" })).is_err());
+    wait_sleep().await;
+    cr.verify(());
+}
+
+#[test]
+async fn restore_keeps_channel_capacity() -> anyhow::Result<()> {
+    let st = StateContainer::new(|cx| EventChannel::<u32>::bounded(1, OverflowPolicy::Block, cx));
+    let json = st.snapshot(serde_json::value::Serializer)?;
+
+    let st2 = StateContainer::new(EventChannel::<u32>::new);
+    st2.restore(json)?;
+    let _es = st2.subscribe_event(|st| st);
+    st2.update(|st, cx| {
+        assert_eq!(st.try_send(1, cx), Ok(()));
+        assert_eq!(st.try_send(2, cx), Err(2));
+    });
+    Ok(())
+}
+
+#[test]
+async fn restore_keeps_channel_replay() -> anyhow::Result<()> {
+    let st = StateContainer::new(|cx| {
+        let mut e = EventChannel::<u32>::new(cx);
+        e.set_replay(Some(Replay::Last(1)));
+        e
+    });
+    let json = st.snapshot(serde_json::value::Serializer)?;
+
+    let st2 = StateContainer::new(EventChannel::<u32>::new);
+    st2.restore(json)?;
+    st2.update(|st, cx| st.send_all([1, 2], cx));
+    let mut es = st2.subscribe_event(|st| st);
+    assert_eq!(es.next().await, Some(2));
+    Ok(())
+}
+
+#[test]
+async fn restore_work_queue_with_owned_subscriber() -> anyhow::Result<()> {
+    let st = StateContainer::new(EventChannel::<u32>::work_queue);
+    let mut es = st.subscribe_event_owned(|st| st);
+    let json = st.snapshot(serde_json::value::Serializer)?;
+
+    st.restore(json)?;
+    assert!(st.update(|st, _| st.is_work_queue()));
+    st.update(|st, cx| st.send(1, cx));
+    assert_eq!(es.next().await, Some(1));
+    Ok(())
+}
+
+#[test]
+async fn bounded_queue_keeps_capacity() -> anyhow::Result<()> {
+    let st = StateContainer::new(|cx| BoundedQueue::new(2, cx));
+    st.update(|st, cx| st.try_push(1, cx)).unwrap();
+    let json = st.snapshot(serde_json::value::Serializer)?;
+    assert_eq!(json, serde_json::json!({ "capacity": 2, "items": [1] }));
+
+    let st2 = StateContainer::new(|cx| BoundedQueue::new(5, cx));
+    st2.restore(json)?;
+    st2.update(|st, cx| {
+        assert_eq!(st.capacity(), 2);
+        assert_eq!(st.try_push(2, cx), Ok(()));
+        assert_eq!(st.try_push(3, cx), Err(3));
+    });
+    assert!(
+        st2.restore(serde_json::json!({ "capacity": 1, "items": [1, 2] }))
+            .is_err()
+    );
+    Ok(())
+}
+
+#[test]
+async fn restore_rejects_zero_channel_capacity() {
+    let st = StateContainer::new(EventChannel::<u32>::new);
+    let json =
+        serde_json::json!({ "capacity": [0, "Block"], "replay": null, "is_work_queue": false });
+    let e = st.restore(json).unwrap_err();
+    assert!(e.to_string().contains("non-zero"), "{e}");
+    assert!(!st.update(|st, _| st.is_full()));
+}
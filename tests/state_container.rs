@@ -1,11 +1,23 @@
 use std::{
-    task::Poll,
+    future::Future,
+    panic::{AssertUnwindSafe, catch_unwind},
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll},
     time::{Duration, Instant},
 };
 
 use assert_call::{Call, CallRecorder, call};
-use futures::StreamExt;
-use sigwake::{StateContainer, StateKey, state::Value};
+use futures::{
+    StreamExt,
+    task::{ArcWake, waker},
+};
+use sigwake::{
+    ContainerPoisoned, PoisonPolicy, StateContainer, StateKey, Timeout, TryUpdateError,
+    state::Value,
+    time::{AnyTime, SpawnAtTask, ThreadTimer, TimerBackend},
+    utils::Action,
+};
 use tokio::{spawn, test, time::sleep};
 
 #[derive(Clone)]
@@ -338,3 +350,241 @@ async fn test_untracked() {
     });
     assert_eq!(*st.lock_untracked().value.get_untracked(), 20);
 }
+
+#[test]
+async fn batch_wakes_after_unlock() {
+    struct Wake(StateContainer<St>);
+    impl ArcWake for Wake {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            let st = arc_self.0.lock_untracked();
+            call!("woken {}", st.a.get_untracked() + st.b.get_untracked());
+        }
+    }
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    let waker = waker(Arc::new(Wake(st.clone())));
+    let mut f = pin!(st.poll_fn(|st, cx| {
+        st.a.get(cx);
+        st.b.get(cx);
+        Poll::<()>::Pending
+    }));
+    assert!(
+        f.as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending()
+    );
+    st.batch(|st, cx| {
+        st.a.set(1, cx);
+        st.b.set(2, cx);
+    });
+    cr.verify("woken 3");
+}
+
+#[test]
+async fn poll_fn_until_ready() -> anyhow::Result<()> {
+    let ss = Ss::new();
+    let task = spawn({
+        let ss = ss.clone();
+        async move {
+            ss.0.poll_fn_until(Duration::from_secs(10), |st, cx| {
+                if *st.a.get(cx) >= 10 {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await
+        }
+    });
+    sleep(Duration::from_millis(100)).await;
+    ss.set_a(10);
+    assert_eq!(task.await?, Ok(()));
+    Ok(())
+}
+
+#[test]
+async fn poll_fn_until_timeout() {
+    let ss = Ss::new();
+    let start = Instant::now();
+    let ret =
+        ss.0.poll_fn_until(Duration::from_millis(100), |_st, _cx| Poll::<()>::Pending)
+            .await;
+    assert_eq!(ret, Err(Timeout));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[test]
+async fn timer_backend() -> anyhow::Result<()> {
+    struct Backend;
+    impl TimerBackend for Backend {
+        fn spawn_at(&self, action: Action, at: AnyTime) -> SpawnAtTask {
+            call!("spawn_at");
+            ThreadTimer.spawn_at(action, at)
+        }
+    }
+    let mut cr = CallRecorder::new();
+    let ss = Ss::new();
+    ss.0.set_timer_backend(Backend);
+    let end = Instant::now() + Duration::from_millis(100);
+    spawn(async move {
+        ss.0.poll_fn(|_st, cx| {
+            cx.notify_at(end);
+            if Instant::now() >= end {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    })
+    .await?;
+    cr.verify("spawn_at");
+    Ok(())
+}
+
+#[test]
+async fn subscribe_without_sync() -> anyhow::Result<()> {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(|cx| (Value::new(0, cx), std::cell::Cell::new(1)));
+
+    let mut stream = st.subscribe(|st, cx| *st.0.get(cx) + st.1.get());
+    spawn(async move {
+        while let Some(value) = stream.next().await {
+            call!("{value}");
+        }
+    });
+    sleep(Duration::from_millis(100)).await;
+    cr.verify("1");
+
+    st.update(|st, cx| st.0.set(2, cx));
+    sleep(Duration::from_millis(100)).await;
+    cr.verify("3");
+    Ok(())
+}
+
+#[test]
+async fn try_update() {
+    let ss = Ss::new();
+    let guard = ss.0.lock_untracked();
+    assert_eq!(
+        ss.0.try_update(|st, cx| st.a.set(1, cx)),
+        Err(TryUpdateError::WouldBlock)
+    );
+    drop(guard);
+    assert_eq!(ss.0.try_update(|st, cx| st.a.set(1, cx)), Ok(()));
+    assert_eq!(*ss.0.lock_untracked().a.get_untracked(), 1);
+}
+
+#[test]
+async fn update_async_does_not_block_thread() -> anyhow::Result<()> {
+    let mut cr = CallRecorder::new();
+    let ss = Ss::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let locker = std::thread::spawn({
+        let ss = ss.clone();
+        move || {
+            let _guard = ss.0.lock_untracked();
+            tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(300));
+        }
+    });
+    rx.recv()?;
+    let ticker = spawn(async {
+        sleep(Duration::from_millis(50)).await;
+        call!("tick");
+    });
+    ss.0.update_async(|st, cx| {
+        call!("update");
+        st.a.set(1, cx);
+    })
+    .await;
+    ticker.await?;
+    cr.verify(["tick", "update"]);
+    locker.join().unwrap();
+    Ok(())
+}
+
+#[test]
+async fn poll_fn_waits_for_lock() -> anyhow::Result<()> {
+    let ss = Ss::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let locker = std::thread::spawn({
+        let ss = ss.clone();
+        move || {
+            let _guard = ss.0.lock_untracked();
+            tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(300));
+        }
+    });
+    rx.recv()?;
+    let mut f = pin!(ss.wait_ab_10());
+    assert!(
+        f.as_mut()
+            .poll(&mut Context::from_waker(futures::task::noop_waker_ref()))
+            .is_pending()
+    );
+    ss.0.update_async(|st, cx| st.a.set(10, cx)).await;
+    assert_eq!(f.await, 10);
+    locker.join().unwrap();
+    Ok(())
+}
+
+fn poison(ss: &Ss) {
+    let r = catch_unwind(AssertUnwindSafe(|| {
+        ss.0.update(|st, cx| {
+            st.a.set(5, cx);
+            panic!("poison");
+        })
+    }));
+    assert!(r.is_err());
+}
+
+#[test]
+async fn poisoned() {
+    let ss = Ss::new();
+    poison(&ss);
+    assert_eq!(
+        ss.0.try_update(|st, cx| st.a.set(1, cx)),
+        Err(TryUpdateError::Poisoned(ContainerPoisoned))
+    );
+    assert_eq!(
+        ss.0.try_poll_fn(|_, _| Poll::Ready(())).await,
+        Err(ContainerPoisoned)
+    );
+    assert!(catch_unwind(AssertUnwindSafe(|| ss.set_a(1))).is_err());
+}
+
+#[test]
+async fn poisoned_recover() -> anyhow::Result<()> {
+    let ss = Ss::new();
+    let task = spawn({
+        let ss = ss.clone();
+        async move { ss.wait_ab_10().await }
+    });
+    sleep(Duration::from_millis(100)).await;
+    poison(&ss);
+    ss.0.set_poison_policy(PoisonPolicy::Recover);
+    assert_eq!(*ss.0.lock_untracked().a.get_untracked(), 5);
+    ss.set_b(5);
+    assert_eq!(task.await?, 10);
+    Ok(())
+}
+
+#[test]
+async fn drop_key_and_target_while_poisoned() {
+    let ss = Ss::new();
+    let mut f = Box::pin(ss.wait_ab_10());
+    assert!(
+        f.as_mut()
+            .poll(&mut Context::from_waker(futures::task::noop_waker_ref()))
+            .is_pending()
+    );
+    let r = catch_unwind(AssertUnwindSafe(|| {
+        ss.0.update(|_, cx| {
+            let _key = StateKey::new(cx);
+            panic!("poison");
+        })
+    }));
+    assert!(r.is_err());
+    drop(f);
+}
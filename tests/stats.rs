@@ -0,0 +1,120 @@
+use std::{
+    future::Future,
+    pin::pin,
+    sync::{Arc, Barrier},
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures::task::noop_waker;
+use sigwake::{StateContainer, StateKey, time, utils::Action};
+
+#[test]
+fn counts_keys_targets_and_edges() {
+    let c = StateContainer::new(|cx| (StateKey::new(cx), StateKey::new(cx)));
+    let s = c.stats();
+    assert_eq!((s.keys, s.targets, s.edges), (2, 0, 0));
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut f = pin!(c.poll_fn(|(a, b), cx| {
+        a.watch(cx);
+        b.watch(cx);
+        Poll::<()>::Pending
+    }));
+    assert!(f.as_mut().poll(&mut cx).is_pending());
+    let s = c.stats();
+    assert_eq!((s.keys, s.targets, s.edges), (2, 1, 2));
+
+    c.update(|(_, b), cx| *b = StateKey::new(cx));
+    let s = c.stats();
+    assert_eq!((s.keys, s.targets, s.edges), (2, 1, 1));
+}
+
+#[test]
+fn counts_notifications_and_wakeups() {
+    let c = StateContainer::new(StateKey::new);
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut f = pin!(c.poll_fn(|a, cx| {
+        a.watch(cx);
+        Poll::<()>::Pending
+    }));
+    assert!(f.as_mut().poll(&mut cx).is_pending());
+
+    c.update(|a, cx| {
+        a.notify(cx);
+        a.notify(cx);
+    });
+    let s = c.stats();
+    assert_eq!((s.notifications, s.wakeups), (2, 1));
+}
+
+#[test]
+fn records_lock_wait() {
+    let c = StateContainer::new(|_| ());
+    assert_eq!(c.stats().lock_contentions, 0);
+
+    let barrier = Arc::new(Barrier::new(2));
+    let t = thread::spawn({
+        let c = c.clone();
+        let barrier = barrier.clone();
+        move || {
+            c.update(|_, _| {
+                barrier.wait();
+                thread::sleep(Duration::from_millis(100));
+            })
+        }
+    });
+    barrier.wait();
+    let start = Instant::now();
+    c.update(|_, _| {});
+    let elapsed = start.elapsed();
+    t.join().unwrap();
+
+    let s = c.stats();
+    assert_eq!(s.lock_contentions, 1);
+    assert!(s.lock_wait > Duration::ZERO);
+    assert!(s.lock_wait <= elapsed);
+}
+
+#[test]
+fn records_async_lock_wait() {
+    let c = StateContainer::new(|_| ());
+    let barrier = Arc::new(Barrier::new(2));
+    let t = thread::spawn({
+        let c = c.clone();
+        let barrier = barrier.clone();
+        move || {
+            c.update(|_, _| {
+                barrier.wait();
+                thread::sleep(Duration::from_millis(100));
+            })
+        }
+    });
+    barrier.wait();
+    let start = Instant::now();
+    futures::executor::block_on(c.update_async(|_, _| {}));
+    let elapsed = start.elapsed();
+    t.join().unwrap();
+
+    let s = c.stats();
+    assert_eq!(s.lock_contentions, 1);
+    assert!(s.lock_wait >= Duration::from_millis(50));
+    assert!(s.lock_wait <= elapsed);
+}
+
+#[test]
+fn timer_stats() {
+    let at = time::now() + Duration::from_secs(3600);
+    let tasks: Vec<_> = (0..3)
+        .map(|_| time::spawn_at(Action::new(|| {}), at))
+        .collect();
+    let s = time::stats();
+    assert_eq!(s.pending, 3);
+    assert!(s.thread_spawns >= 1);
+
+    drop(tasks);
+    assert_eq!(time::stats().pending, 0);
+}
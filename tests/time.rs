@@ -1,4 +1,5 @@
 use assert_call::{CallRecorder, call};
+use futures::StreamExt;
 use sigwake::time::spawn_at;
 use sigwake::utils::Action;
 use std::time::{Duration, Instant, SystemTime};
@@ -165,3 +166,92 @@ async fn sleep_system_time_elapsed() {
         "sleep should wait until the target time"
     );
 }
+
+#[test]
+async fn stream_until() {
+    let s = futures::stream::iter([1, 2]).chain(futures::stream::pending());
+    let items = sigwake::time::stream_until(s, Duration::from_millis(50))
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(items, vec![Ok(1), Ok(2), Err(sigwake::Timeout)]);
+}
+
+#[test]
+async fn stream_until_end() {
+    let s = futures::stream::iter([1, 2]);
+    let items = sigwake::time::stream_until(s, Duration::from_millis(50))
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(items, vec![Ok(1), Ok(2)]);
+}
+
+#[test]
+async fn interval() {
+    let start = Instant::now();
+    let period = Duration::from_millis(30);
+    let ticks = sigwake::time::interval(period)
+        .take(3)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(ticks.len(), 3);
+    assert!(ticks[0] >= start);
+    assert_eq!(ticks[1] - ticks[0], period);
+    assert_eq!(ticks[2] - ticks[1], period);
+    assert!(Instant::now() >= ticks[2]);
+}
+
+#[test]
+async fn spawn_every() {
+    let mut cr = CallRecorder::new();
+    let task = sigwake::time::spawn_every(
+        || call!("tick"),
+        Duration::from_millis(80),
+        sigwake::time::MissedTickBehavior::Skip,
+    );
+    sleep(Duration::from_millis(120)).await;
+    cr.verify("tick");
+    sleep(Duration::from_millis(80)).await;
+    cr.verify("tick");
+    drop(task);
+    wait_sleep().await;
+    cr.verify(());
+}
+
+#[test]
+async fn spawn_every_drop_waits_for_call() {
+    let mut cr = CallRecorder::new();
+    let task = sigwake::time::spawn_every(
+        || {
+            call!("start");
+            std::thread::sleep(Duration::from_millis(200));
+            call!("end");
+        },
+        Duration::from_millis(50),
+        sigwake::time::MissedTickBehavior::Skip,
+    );
+    sleep(Duration::from_millis(100)).await;
+    cr.verify("start");
+    drop(task);
+    cr.verify("end");
+    wait_sleep().await;
+    cr.verify(());
+}
+
+#[test]
+async fn spawn_every_drop_inside_call() {
+    let mut cr = CallRecorder::new();
+    let task = std::sync::Arc::new(std::sync::Mutex::new(None));
+    *task.lock().unwrap() = Some(sigwake::time::spawn_every(
+        {
+            let task = task.clone();
+            move || {
+                call!("tick");
+                drop(task.lock().unwrap().take());
+            }
+        },
+        Duration::from_millis(50),
+        sigwake::time::MissedTickBehavior::Skip,
+    ));
+    wait_sleep().await;
+    cr.verify("tick");
+}
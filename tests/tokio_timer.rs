@@ -0,0 +1,33 @@
+#![cfg(feature = "tokio")]
+
+use std::time::{Duration, Instant};
+
+use assert_call::{CallRecorder, call};
+use sigwake::{
+    time::{TokioTimer, set_backend, sleep, spawn_at},
+    utils::Action,
+};
+use tokio::test;
+
+// The backend is process-wide, so everything that uses it runs in a single runtime.
+#[test]
+async fn tokio_backend() {
+    set_backend(TokioTimer::current());
+    let mut cr = CallRecorder::new();
+
+    let _task = spawn_at(Action::new(|| call!("called")), Duration::from_millis(50));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    cr.verify("called");
+
+    let task = spawn_at(
+        Action::new(|| call!("cancelled")),
+        Duration::from_millis(50),
+    );
+    drop(task);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    cr.verify(());
+
+    let start = Instant::now();
+    sleep(Duration::from_millis(50)).await;
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
@@ -0,0 +1,154 @@
+#![cfg(feature = "tracing")]
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    future::Future,
+    pin::pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{StreamExt, task::noop_waker};
+use sigwake::{
+    StateContainer, StateKey,
+    state::Memo,
+    time::{self, ManualClock},
+};
+use tracing::{
+    Event, Metadata, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    subscriber::with_default,
+};
+
+/// A subscriber that records each entered span and each event as a line.
+#[derive(Default)]
+struct Recorder {
+    lines: Arc<Mutex<Vec<String>>>,
+    spans: Mutex<HashMap<u64, String>>,
+    next_id: AtomicU64,
+}
+
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            write!(self.0, "{value:?}").unwrap();
+        } else {
+            write!(self.0, " {}={value:?}", field.name()).unwrap();
+        }
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut fields = Fields(span.metadata().name().to_string());
+        span.record(&mut fields);
+        self.spans.lock().unwrap().insert(id, fields.0);
+        Id::from_u64(id)
+    }
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields(String::new());
+        event.record(&mut fields);
+        self.lines.lock().unwrap().push(fields.0);
+    }
+    fn enter(&self, span: &Id) {
+        let name = self.spans.lock().unwrap()[&span.into_u64()].clone();
+        self.lines.lock().unwrap().push(format!("enter {name}"));
+    }
+    fn exit(&self, _: &Id) {}
+}
+
+fn record(f: impl FnOnce()) -> Vec<String> {
+    let recorder = Recorder::default();
+    let lines = recorder.lines.clone();
+    with_default(recorder, f);
+    lines.lock().unwrap().clone()
+}
+
+fn poll<F: Future>(f: std::pin::Pin<&mut F>) -> Poll<F::Output> {
+    let waker = noop_waker();
+    f.poll(&mut Context::from_waker(&waker))
+}
+
+#[test]
+fn update_and_notify() {
+    let c = StateContainer::new(|cx| StateKey::new_named("a", cx));
+    let lines = record(|| c.update(|a, cx| a.notify(cx)));
+    assert_eq!(lines, ["enter update", r#"notify key=0 name="a""#]);
+}
+
+#[test]
+fn subscribe_recomputation() {
+    let c = StateContainer::new(StateKey::new);
+    let mut s = c.subscribe(|a, cx| a.watch(cx));
+    let lines = record(|| {
+        assert!(poll(pin!(s.next())).is_ready());
+        c.update(|a, cx| a.notify(cx));
+        assert!(poll(pin!(s.next())).is_ready());
+    });
+    assert_eq!(
+        lines,
+        [
+            "enter subscribe",
+            "enter update",
+            "notify key=0",
+            "target woken target_id=0 is_dirty=true was_clean=true",
+            "enter subscribe target_id=0",
+        ]
+    );
+}
+
+#[test]
+fn memo_recomputation() {
+    let c = StateContainer::new(|cx| (StateKey::new(cx), Memo::<u32>::new(cx)));
+    let lines = record(|| {
+        c.update(|(a, memo), cx| {
+            memo.get(cx, |cx| {
+                a.watch(cx);
+                1
+            });
+        })
+    });
+    assert_eq!(lines, ["enter update", "enter recompute key=1"]);
+}
+
+#[test]
+fn notify_at_registered_and_fired() {
+    let clock = ManualClock::install();
+    let c = StateContainer::new(|_| ());
+    let at = time::now() + Duration::from_millis(10);
+    let lines = record(|| {
+        let mut f = pin!(c.poll_fn(|_, cx| {
+            if time::now() >= at {
+                return Poll::Ready(());
+            }
+            cx.notify_at(at);
+            Poll::Pending
+        }));
+        assert!(poll(f.as_mut()).is_pending());
+        clock.advance(Duration::from_millis(10));
+        assert!(poll(f.as_mut()).is_ready());
+    });
+    assert_eq!(
+        lines,
+        [
+            "enter poll_fn",
+            &format!("notify_at registered target_id=0 at={at:?}"),
+            "notify_at fired target_id=0",
+            "enter poll_fn target_id=0",
+        ]
+    );
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use assert_call::{CallRecorder, call};
+use futures::StreamExt;
+use sigwake::{StateContainer, state::Value};
+use tokio::{spawn, test, time::sleep};
+
+struct St {
+    value: Value<u32>,
+}
+impl St {
+    fn new() -> StateContainer<Self> {
+        StateContainer::new(|cx| Self {
+            value: Value::new(0, cx),
+        })
+    }
+}
+
+async fn wait_sleep() {
+    sleep(Duration::from_millis(100)).await;
+}
+
+fn watch(st: &StateContainer<St>) {
+    let mut s = st.subscribe(|st, cx| *st.value.get(cx));
+    spawn(async move {
+        while let Some(value) = s.next().await {
+            call!("{value}");
+        }
+    });
+}
+
+#[test]
+async fn set_if_changed() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    watch(&st);
+    wait_sleep().await;
+    cr.verify("0");
+
+    assert!(!st.update(|st, cx| st.value.set_if_changed(0, cx)));
+    wait_sleep().await;
+    cr.verify(());
+
+    assert!(st.update(|st, cx| st.value.set_if_changed(1, cx)));
+    wait_sleep().await;
+    cr.verify("1");
+}
+
+#[test]
+async fn set_if_changed_by() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    watch(&st);
+    wait_sleep().await;
+    cr.verify("0");
+
+    let eq = |a: &u32, b: &u32| a / 10 == b / 10;
+    assert!(!st.update(|st, cx| st.value.set_if_changed_by(5, eq, cx)));
+    wait_sleep().await;
+    cr.verify(());
+
+    assert!(st.update(|st, cx| st.value.set_if_changed_by(15, eq, cx)));
+    wait_sleep().await;
+    cr.verify("15");
+}
+
+#[test]
+async fn update_with() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    watch(&st);
+    wait_sleep().await;
+    cr.verify("0");
+
+    assert!(!st.update(|st, cx| st.value.update_with(|v| (*v).min(10), cx)));
+    wait_sleep().await;
+    cr.verify(());
+
+    assert!(st.update(|st, cx| st.value.update_with(|v| v + 2, cx)));
+    wait_sleep().await;
+    cr.verify("2");
+}
+
+#[test]
+async fn replace() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    watch(&st);
+    wait_sleep().await;
+    cr.verify("0");
+
+    assert_eq!(st.update(|st, cx| st.value.replace(3, cx)), 0);
+    wait_sleep().await;
+    cr.verify("3");
+}
+
+#[test]
+async fn get_mut_without_modification() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    watch(&st);
+    wait_sleep().await;
+    cr.verify("0");
+
+    assert_eq!(st.update(|st, cx| *st.value.get_mut(cx)), 0);
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| *st.value.get_mut(cx) += 4);
+    wait_sleep().await;
+    cr.verify("4");
+}
+
+#[test]
+async fn get_mut_if_changed() {
+    let mut cr = CallRecorder::new();
+    let st = St::new();
+    watch(&st);
+    wait_sleep().await;
+    cr.verify("0");
+
+    st.update(|st, cx| *st.value.get_mut_if_changed(cx) = 0);
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| {
+        let mut value = st.value.get_mut_if_changed(cx);
+        *value = 5;
+        *value = 0;
+    });
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| *st.value.get_mut_if_changed(cx) = 6);
+    wait_sleep().await;
+    cr.verify("6");
+}
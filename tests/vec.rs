@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use assert_call::{Call, CallRecorder, call};
+use futures::StreamExt;
+use sigwake::{StateContainer, state::Vec};
+use tokio::{spawn, test, time::sleep};
+
+async fn wait_sleep() {
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[test]
+async fn get_watches_only_its_index() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(Vec::<u32>::new);
+    st.update(|st, cx| {
+        st.push(10, cx);
+        st.push(20, cx);
+    });
+    let mut s = st.subscribe(|st, cx| st.get(0, cx).copied());
+    spawn(async move {
+        while let Some(value) = s.next().await {
+            call!("{value:?}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("Some(10)");
+
+    st.update(|st, cx| st.set(1, 21, cx));
+    st.update(|st, cx| st.push(30, cx));
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| st.insert(0, 5, cx));
+    wait_sleep().await;
+    cr.verify("Some(5)");
+
+    st.update(|st, cx| st.remove(0, cx));
+    wait_sleep().await;
+    cr.verify("Some(10)");
+
+    st.update(|st, cx| st.clear(cx));
+    wait_sleep().await;
+    cr.verify("None");
+
+    st.update(|st, cx| st.push(1, cx));
+    wait_sleep().await;
+    cr.verify("Some(1)");
+}
+
+#[test]
+async fn len_and_iter() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(Vec::<u32>::new);
+    let mut s_len = st.subscribe(|st, cx| st.len(cx));
+    spawn(async move {
+        while let Some(len) = s_len.next().await {
+            call!("len {len}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("len 0");
+
+    let mut s_sum = st.subscribe(|st, cx| st.iter(cx).sum::<u32>());
+    spawn(async move {
+        while let Some(sum) = s_sum.next().await {
+            call!("sum {sum}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("sum 0");
+
+    st.update(|st, cx| st.push(1, cx));
+    wait_sleep().await;
+    cr.verify(Call::par(["len 1", "sum 1"]));
+
+    st.update(|st, cx| *st.get_mut(0, cx).unwrap() = 2);
+    wait_sleep().await;
+    cr.verify("sum 2");
+
+    assert_eq!(st.update(|st, cx| st.pop(cx)), Some(2));
+    wait_sleep().await;
+    cr.verify(Call::par(["len 0", "sum 0"]));
+}
+
+#[test]
+async fn get_mut_without_modification() {
+    let mut cr = CallRecorder::new();
+    let st = StateContainer::new(Vec::<u32>::new);
+    st.update(|st, cx| st.push(1, cx));
+    let mut s = st.subscribe(|st, cx| st.iter(cx).sum::<u32>());
+    spawn(async move {
+        while let Some(sum) = s.next().await {
+            call!("sum {sum}");
+        }
+    });
+    wait_sleep().await;
+    cr.verify("sum 1");
+
+    assert_eq!(st.update(|st, cx| *st.get_mut(0, cx).unwrap()), 1);
+    wait_sleep().await;
+    cr.verify(());
+
+    st.update(|st, cx| *st.get_mut(0, cx).unwrap() += 1);
+    wait_sleep().await;
+    cr.verify("sum 2");
+}